@@ -0,0 +1,134 @@
+use crate::{
+    ray::Ray,
+    rtweekend::INFINITY,
+    vec3::{Point3, Vec3},
+};
+
+// Axis-aligned bounding box, stored as its minimum and maximum corners
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::empty()
+    }
+}
+
+impl Aabb {
+    pub fn new(a: Point3, b: Point3) -> Self {
+        // Treat the two points as extrema without requiring a particular order
+        Self {
+            min: Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(INFINITY, INFINITY, INFINITY),
+            max: Point3::new(-INFINITY, -INFINITY, -INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|a| self.min[a] > self.max[a])
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point3::new(
+                self.min.x().min(other.min.x()),
+                self.min.y().min(other.min.y()),
+                self.min.z().min(other.min.z()),
+            ),
+            max: Point3::new(
+                self.max.x().max(other.max.x()),
+                self.max.y().max(other.max.y()),
+                self.max.z().max(other.max.z()),
+            ),
+        }
+    }
+
+    // Widen any axis thinner than delta so flat primitives still have a hittable volume
+    pub fn pad(&self, delta: f64) -> Aabb {
+        let mut padded = *self;
+        for a in 0..3 {
+            if padded.max[a] - padded.min[a] < delta {
+                padded.min[a] -= delta / 2.0;
+                padded.max[a] += delta / 2.0;
+            }
+        }
+        padded
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        std::array::from_fn(|i| {
+            Point3::new(
+                if i & 1 == 0 {
+                    self.min.x()
+                } else {
+                    self.max.x()
+                },
+                if i & 2 == 0 {
+                    self.min.y()
+                } else {
+                    self.max.y()
+                },
+                if i & 4 == 0 {
+                    self.min.z()
+                } else {
+                    self.max.z()
+                },
+            )
+        })
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.extent();
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.interval(r, t_min, t_max).is_some()
+    }
+
+    // The part of [t_min, t_max] during which r is inside the box
+    pub fn interval(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        // Slab test: intersect the ray's parameter range with each pair of planes
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for a in 0..3 {
+            let inv_d = 1.0 / r.direction()[a];
+            let mut t0 = (self.min[a] - r.origin()[a]) * inv_d;
+            let mut t1 = (self.max[a] - r.origin()[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // Written so that a NaN from 0 * inf leaves the range untouched
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{
+    environment::EnvironmentMap,
+    ray::Ray,
+    sampler::Sampler,
+    sky::Sky,
+    vec3::{unit_vector, Color, Vec3},
+};
+
+// Radiance arriving along rays that escape the scene without hitting anything
+pub enum Background {
+    None,
+    Solid(Color),
+    // Blend from `bottom` looking straight down to `top` looking straight up
+    Gradient { bottom: Color, top: Color },
+    // An HDR image around the scene, sampled like a light
+    Environment(EnvironmentMap),
+    // Analytic daylight with a sun that is sampled like a light
+    Sky(Sky),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+impl Background {
+    pub fn value(&self, r: &Ray) -> Color {
+        match self {
+            Background::None => Color::new(0.0, 0.0, 0.0),
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let unit_direction = unit_vector(r.direction());
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                *bottom * (1.0 - t) + *top * t
+            }
+            Background::Environment(map) => map.value(&r.direction()),
+            Background::Sky(sky) => sky.value(&r.direction()),
+        }
+    }
+
+    // Whether shadow rays are aimed at the background as well as at the scene's lights.
+    // Smooth backgrounds are left to BSDF sampling, which finds them just as well.
+    pub fn is_sampleable(&self) -> bool {
+        matches!(self, Background::Environment(_) | Background::Sky(_))
+    }
+
+    // Like Hittable::pdf_value, for a light infinitely far away in every direction
+    pub fn pdf_value(&self, direction: &Vec3) -> f64 {
+        match self {
+            Background::Environment(map) => map.pdf_value(direction),
+            Background::Sky(sky) => sky.pdf_value(direction),
+            _ => 0.0,
+        }
+    }
+
+    pub fn random(&self, sampler: &mut Sampler) -> Vec3 {
+        match self {
+            Background::Environment(map) => map.random(sampler),
+            Background::Sky(sky) => sky.random(sampler),
+            _ => Vec3::new(0.0, 1.0, 0.0),
+        }
+    }
+}
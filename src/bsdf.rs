@@ -0,0 +1,668 @@
+use crate::{
+    microfacet::TrowbridgeReitz,
+    onb::Onb,
+    rtweekend::PI,
+    sampler::Sampler,
+    vec3::{dot, refract, unit_vector, Color, Vec3},
+};
+
+// Scattering at one particular hit, set up by Material::scatter. Directions are unit vectors
+// pointing away from the surface, towards where the light comes from.
+pub trait Bsdf {
+    // BSDF times the cosine between direction and the shading normal
+    fn eval(&self, direction: &Vec3) -> Color;
+
+    // Density, over solid angle, with which sample picks direction
+    fn pdf(&self, direction: &Vec3) -> f64;
+
+    // None when the sampled direction carries no light, e.g. it points into the surface
+    fn sample(&self, sampler: &mut Sampler) -> Option<Vec3>;
+}
+
+// Cosine-weighted hemisphere direction around +z
+pub fn random_cosine_direction(sampler: &mut Sampler) -> Vec3 {
+    let r1 = sampler.random_double();
+    let r2 = sampler.random_double();
+    let phi = 2.0 * PI * r1;
+    let x = f64::cos(phi) * r2.sqrt();
+    let y = f64::sin(phi) * r2.sqrt();
+    let z = f64::sqrt(1.0 - r2);
+    Vec3::new(x, y, z)
+}
+
+// Ideal diffuse reflection, importance sampled by the cosine term
+pub struct DiffuseBsdf {
+    albedo: Color,
+    uvw: Onb,
+}
+
+impl DiffuseBsdf {
+    pub fn new(albedo: Color, normal: Vec3) -> Self {
+        Self {
+            albedo,
+            uvw: Onb::build_from_w(normal),
+        }
+    }
+}
+
+impl Bsdf for DiffuseBsdf {
+    fn eval(&self, direction: &Vec3) -> Color {
+        self.albedo * self.pdf(direction)
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let cosine = dot(unit_vector(*direction), self.uvw.w());
+        f64::max(cosine, 0.0) / PI
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<Vec3> {
+        Some(self.uvw.local(random_cosine_direction(sampler)))
+    }
+}
+
+// Scattering inside a participating medium, following the Henyey-Greenstein phase function.
+// g is the mean cosine between the incoming and scattered directions: positive values favour
+// forward scattering, negative ones back scattering and zero scatters isotropically.
+pub struct PhaseBsdf {
+    albedo: Color,
+    g: f64,
+    forward: Onb, // w is the direction the incoming ray was travelling in
+}
+
+impl PhaseBsdf {
+    pub fn new(albedo: Color, g: f64, incoming: Vec3) -> Self {
+        Self {
+            albedo,
+            g: g.clamp(-0.99, 0.99),
+            forward: Onb::build_from_w(incoming),
+        }
+    }
+
+    fn phase(&self, cosine: f64) -> f64 {
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * cosine;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+}
+
+impl Bsdf for PhaseBsdf {
+    // There is no surface, so no cosine term either
+    fn eval(&self, direction: &Vec3) -> Color {
+        self.albedo * self.pdf(direction)
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        self.phase(dot(unit_vector(*direction), self.forward.w()))
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<Vec3> {
+        let g = self.g;
+        let r1 = sampler.random_double();
+        let r2 = sampler.random_double();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * r1
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * r1);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
+        let phi = 2.0 * PI * r2;
+        Some(self.forward.local(Vec3::new(
+            f64::cos(phi) * sin_theta,
+            f64::sin(phi) * sin_theta,
+            cos_theta,
+        )))
+    }
+}
+
+// Fraction of light a surface reflects, given the cosine between the incoming direction and
+// the (micro)surface normal
+#[derive(Clone, Copy)]
+pub enum Fresnel {
+    // Schlick's approximation, starting from the color reflected at normal incidence
+    Schlick(Color),
+    // Exact reflectance of a metal with complex index of refraction eta + ik, per channel
+    Conductor { eta: Color, k: Color },
+}
+
+impl Fresnel {
+    pub fn reflectance(&self, cos_theta: f64) -> Color {
+        let cos_theta = cos_theta.clamp(0.0, 1.0);
+        match self {
+            Fresnel::Schlick(f0) => {
+                *f0 + (Color::new(1.0, 1.0, 1.0) - *f0) * f64::powi(1.0 - cos_theta, 5)
+            }
+            Fresnel::Conductor { eta, k } => Color::new(
+                fresnel_conductor(cos_theta, eta.x(), k.x()),
+                fresnel_conductor(cos_theta, eta.y(), k.y()),
+                fresnel_conductor(cos_theta, eta.z(), k.z()),
+            ),
+        }
+    }
+}
+
+// Unpolarized reflectance at the boundary between air and a conductor
+fn fresnel_conductor(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta * cos_theta;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = f64::sqrt(t0 * t0 + 4.0 * eta2 * k2);
+    let a = f64::sqrt(0.5 * (a2_plus_b2 + t0).max(0.0));
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos_theta * a;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rs + rp)
+}
+
+// Glossy reflection off a rough surface made of perfectly mirroring microfacets with the GGX
+// distribution, as for metals. Directions are sampled from the visible normals, so eval / pdf
+// stays close to the Fresnel color.
+pub struct MicrofacetReflection {
+    distribution: TrowbridgeReitz,
+    fresnel: Fresnel,
+    frame: Onb,
+    wo: Vec3, // Direction back along the incoming ray, in the local frame
+}
+
+impl MicrofacetReflection {
+    pub fn new(
+        distribution: TrowbridgeReitz,
+        fresnel: Fresnel,
+        normal: Vec3,
+        incoming: Vec3,
+    ) -> Self {
+        let frame = Onb::build_from_w(normal);
+        Self {
+            distribution,
+            fresnel,
+            frame,
+            wo: frame.world_to_local(-unit_vector(incoming)),
+        }
+    }
+
+    // Line the distribution's x axis up with tangent, which only matters when it is
+    // anisotropic
+    pub fn with_tangent(mut self, tangent: Vec3) -> Self {
+        let wo = self.frame.local(self.wo);
+        self.frame = Onb::build_from_w_and_u(self.frame.w(), tangent);
+        self.wo = self.frame.world_to_local(wo);
+        self
+    }
+}
+
+impl Bsdf for MicrofacetReflection {
+    fn eval(&self, direction: &Vec3) -> Color {
+        let wi = self.frame.world_to_local(unit_vector(*direction));
+        let wo = self.wo;
+        if wi.z() <= 0.0 || wo.z() <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let h = unit_vector(wi + wo);
+        let d = self.distribution.d(&h);
+        let g = self.distribution.g(&wo, &wi);
+        // D G F / (4 cos_o cos_i), times cos_i
+        self.fresnel.reflectance(dot(wo, h)) * (d * g / (4.0 * wo.z()))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let wi = self.frame.world_to_local(unit_vector(*direction));
+        if wi.z() <= 0.0 || self.wo.z() <= 0.0 {
+            return 0.0;
+        }
+        let h = unit_vector(wi + self.wo);
+        // Reflecting about h doubles angles, which squeezes solid angle by 4 (wo . h)
+        self.distribution.visible_pdf(&self.wo, &h) / (4.0 * dot(self.wo, h))
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<Vec3> {
+        if self.wo.z() <= 0.0 {
+            return None;
+        }
+        let h = self.distribution.sample_visible(
+            &self.wo,
+            sampler.random_double(),
+            sampler.random_double(),
+        );
+        let wi = h * (2.0 * dot(self.wo, h)) - self.wo;
+        if wi.z() <= 0.0 {
+            return None;
+        }
+        Some(self.frame.local(wi))
+    }
+}
+
+// Exact unpolarized reflectance for light arriving at cos_theta to the normal of a boundary
+// between dielectrics, where eta is the index on the far side over the index on the near side
+pub fn fresnel_dielectric(cos_theta: f64, eta: f64) -> f64 {
+    let cos_i = cos_theta.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+    let cos_t = f64::sqrt(1.0 - sin2_t);
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// Reflection and refraction through a rough boundary between dielectrics, like frosted glass,
+// after Walter et al., "Microfacet Models for Refraction through Rough Surfaces" (2007). A
+// visible microfacet normal is sampled, then reflection or refraction through it is picked in
+// proportion to the Fresnel reflectance.
+pub struct MicrofacetDielectric {
+    distribution: TrowbridgeReitz,
+    eta: f64, // Index of refraction beyond the surface over the one on the incoming side
+    frame: Onb,
+    wo: Vec3,
+}
+
+impl MicrofacetDielectric {
+    // normal faces the incoming ray, and eta is as seen from that side
+    pub fn new(distribution: TrowbridgeReitz, eta: f64, normal: Vec3, incoming: Vec3) -> Self {
+        let frame = Onb::build_from_w(normal);
+        Self {
+            distribution,
+            eta,
+            frame,
+            wo: frame.world_to_local(-unit_vector(incoming)),
+        }
+    }
+
+    // The microfacet normal that turns wo into wi, on the same side as wo, or None when wi is
+    // parallel to the surface or no microfacet facing wo could produce it
+    fn half_vector(&self, wi: &Vec3) -> Option<Vec3> {
+        let wo = self.wo;
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return None;
+        }
+        let reflect = wi.z() > 0.0;
+        let eta = if reflect { 1.0 } else { self.eta };
+        let h = *wi * eta + wo;
+        if h.length_squared() == 0.0 {
+            return None;
+        }
+        let h = unit_vector(h);
+        let h = if h.z() < 0.0 { -h } else { h };
+        // Both directions must see the front of the microfacet they scatter off
+        if dot(h, *wi) * wi.z() <= 0.0 || dot(h, wo) <= 0.0 {
+            return None;
+        }
+        Some(h)
+    }
+}
+
+impl Bsdf for MicrofacetDielectric {
+    fn eval(&self, direction: &Vec3) -> Color {
+        let wi = self.frame.world_to_local(unit_vector(*direction));
+        let Some(h) = self.half_vector(&wi) else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+        let wo = self.wo;
+        let d = self.distribution.d(&h);
+        let g = self.distribution.g(&wo, &wi);
+        let f = fresnel_dielectric(dot(wo, h), self.eta);
+        let value = if wi.z() > 0.0 {
+            d * g * f / (4.0 * wo.z())
+        } else {
+            // Radiance isn't scaled by eta squared on crossing, matching smooth Dielectric, so
+            // the two agree as roughness goes to 0
+            let denominator = dot(wi, h) + dot(wo, h) / self.eta;
+            d * g * (1.0 - f) * (dot(wi, h) * dot(wo, h)).abs()
+                / (wo.z() * denominator * denominator)
+        };
+        Color::new(value, value, value)
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let wi = self.frame.world_to_local(unit_vector(*direction));
+        let Some(h) = self.half_vector(&wi) else {
+            return 0.0;
+        };
+        let wo = self.wo;
+        let f = fresnel_dielectric(dot(wo, h), self.eta);
+        let pdf_h = self.distribution.visible_pdf(&wo, &h);
+        if wi.z() > 0.0 {
+            pdf_h / (4.0 * dot(wo, h)) * f
+        } else {
+            let denominator = dot(wi, h) + dot(wo, h) / self.eta;
+            pdf_h * dot(wi, h).abs() / (denominator * denominator) * (1.0 - f)
+        }
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<Vec3> {
+        let wo = self.wo;
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let h =
+            self.distribution
+                .sample_visible(&wo, sampler.random_double(), sampler.random_double());
+        let cos_o = dot(wo, h);
+        let wi = if sampler.random_double() < fresnel_dielectric(cos_o, self.eta) {
+            let wi = h * (2.0 * cos_o) - wo;
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            // Refraction is always possible here, since total internal reflection has a
+            // Fresnel reflectance of one
+            let wi = refract(&-wo, &h, 1.0 / self.eta);
+            if wi.z() >= 0.0 {
+                return None;
+            }
+            wi
+        };
+        Some(self.frame.local(wi))
+    }
+}
+
+// A weighted sum of BSDFs, such as the layers of a principled material. Each lobe is sampled
+// with its own probability, which only needs to roughly follow how much light it carries.
+#[derive(Default)]
+pub struct CompositeBsdf {
+    lobes: Vec<(Box<dyn Bsdf>, Color, f64)>, // BSDF, weight and sampling probability
+    total_probability: f64,
+}
+
+impl CompositeBsdf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, bsdf: Box<dyn Bsdf>, weight: Color, probability: f64) {
+        if weight.near_zero() {
+            return;
+        }
+        // Every lobe that adds light has to be sampled now and then
+        let probability = probability.max(1e-3);
+        self.total_probability += probability;
+        self.lobes.push((bsdf, weight, probability));
+    }
+}
+
+impl Bsdf for CompositeBsdf {
+    fn eval(&self, direction: &Vec3) -> Color {
+        self.lobes
+            .iter()
+            .fold(Color::new(0.0, 0.0, 0.0), |sum, (bsdf, weight, _)| {
+                sum + *weight * bsdf.eval(direction)
+            })
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        if self.total_probability <= 0.0 {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .lobes
+            .iter()
+            .map(|(bsdf, _, probability)| probability * bsdf.pdf(direction))
+            .sum();
+        sum / self.total_probability
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<Vec3> {
+        let mut u = sampler.random_double() * self.total_probability;
+        for (bsdf, _, probability) in &self.lobes {
+            if u < *probability {
+                return bsdf.sample(sampler);
+            }
+            u -= probability;
+        }
+        self.lobes
+            .last()
+            .and_then(|(bsdf, _, _)| bsdf.sample(sampler))
+    }
+}
+
+// The diffuse part of the Disney principled BSDF (Burley, "Physically Based Shading at
+// Disney", 2012): Lambertian with a retro-reflective rim on rough surfaces, blended towards a
+// flatter approximation of subsurface scattering, plus a white sheen at grazing angles
+pub struct DisneyDiffuse {
+    base_color: Color,
+    roughness: f64,
+    subsurface: f64,
+    sheen: f64,
+    frame: Onb,
+    wo: Vec3,
+}
+
+impl DisneyDiffuse {
+    pub fn new(
+        base_color: Color,
+        roughness: f64,
+        subsurface: f64,
+        sheen: f64,
+        normal: Vec3,
+        incoming: Vec3,
+    ) -> Self {
+        let frame = Onb::build_from_w(normal);
+        Self {
+            base_color,
+            roughness,
+            subsurface,
+            sheen,
+            frame,
+            wo: frame.world_to_local(-unit_vector(incoming)),
+        }
+    }
+}
+
+impl Bsdf for DisneyDiffuse {
+    fn eval(&self, direction: &Vec3) -> Color {
+        let wi = self.frame.world_to_local(unit_vector(*direction));
+        let (cos_i, cos_o) = (wi.z(), self.wo.z());
+        if cos_i <= 0.0 || cos_o <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let cos_d = dot(wi, unit_vector(wi + self.wo));
+        let fl = f64::powi(1.0 - cos_i, 5);
+        let fv = f64::powi(1.0 - cos_o, 5);
+        let fd90 = 0.5 + 2.0 * cos_d * cos_d * self.roughness;
+        let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+        let fss90 = cos_d * cos_d * self.roughness;
+        let fss = (1.0 + (fss90 - 1.0) * fl) * (1.0 + (fss90 - 1.0) * fv);
+        let ss = 1.25 * (fss * (1.0 / (cos_i + cos_o) - 0.5) + 0.5);
+        let diffuse = self.base_color * ((fd + (ss - fd) * self.subsurface) / PI);
+        let sheen = self.sheen * f64::powi(1.0 - cos_d, 5);
+        (diffuse + Color::new(sheen, sheen, sheen)) * cos_i
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let cosine = dot(unit_vector(*direction), self.frame.w());
+        f64::max(cosine, 0.0) / PI
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<Vec3> {
+        Some(self.frame.local(random_cosine_direction(sampler)))
+    }
+}
+
+// Light that crosses a smooth dielectric coat on its way to and from the base below. The coat
+// reflects the fraction the Fresnel equations give on the way in and out, so the base only
+// receives what is left. The bending of directions inside the coat is ignored.
+pub struct CoatedBsdf {
+    base: Box<dyn Bsdf>,
+    eta: f64, // Index of refraction of the coat over that of the outside
+    normal: Vec3,
+}
+
+impl CoatedBsdf {
+    pub fn new(base: Box<dyn Bsdf>, eta: f64, normal: Vec3) -> Self {
+        Self { base, eta, normal }
+    }
+}
+
+impl Bsdf for CoatedBsdf {
+    fn eval(&self, direction: &Vec3) -> Color {
+        let cosine = dot(unit_vector(*direction), self.normal).abs();
+        self.base.eval(direction) * (1.0 - fresnel_dielectric(cosine, self.eta))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        self.base.pdf(direction)
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<Vec3> {
+        self.base.sample(sampler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::random_unit_vector;
+
+    #[test]
+    fn diffuse_samples_follow_pdf() {
+        // With cosine sampling eval / pdf is the albedo for every sampled direction, and the
+        // pdf integrates to one over the hemisphere
+        let bsdf = DiffuseBsdf::new(Color::new(0.5, 0.25, 1.0), Vec3::new(0.0, 1.0, 0.0));
+        let mut sampler = Sampler::new(5);
+        for _ in 0..100 {
+            let direction = bsdf.sample(&mut sampler).unwrap();
+            let ratio = bsdf.eval(&direction) / bsdf.pdf(&direction);
+            assert!((ratio.y() - 0.25).abs() < 1e-9);
+        }
+        let n = 200_000;
+        let total: f64 = (0..n)
+            .map(|_| {
+                let direction = random_unit_vector(&mut sampler);
+                bsdf.pdf(&direction) * 4.0 * PI
+            })
+            .sum();
+        assert!((total / n as f64 - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn phase_samples_follow_pdf() {
+        let incoming = Vec3::new(0.0, 0.0, -1.0);
+        let mut sampler = Sampler::new(6);
+        for g in [-0.6, 0.0, 0.8] {
+            let bsdf = PhaseBsdf::new(Color::new(0.9, 0.9, 0.9), g, incoming);
+            let n = 200_000;
+            let mut mean_cosine = 0.0;
+            for _ in 0..n {
+                let direction = bsdf.sample(&mut sampler).unwrap();
+                let ratio = bsdf.eval(&direction) / bsdf.pdf(&direction);
+                assert!((ratio.x() - 0.9).abs() < 1e-9);
+                mean_cosine += dot(direction, incoming) / n as f64;
+            }
+            assert!((mean_cosine - g).abs() < 0.01, "g = {}: {}", g, mean_cosine);
+
+            let total: f64 = (0..n)
+                .map(|_| bsdf.pdf(&random_unit_vector(&mut sampler)) * 4.0 * PI)
+                .sum();
+            assert!((total / n as f64 - 1.0).abs() < 0.05, "g = {}", g);
+        }
+    }
+
+    #[test]
+    fn microfacet_samples_follow_pdf() {
+        // Importance sampling and uniform sampling agree on the reflected energy. A white
+        // surface keeps most of it, losing only what would take several bounces to escape.
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let incoming = Vec3::new(0.3, -1.0, 0.1);
+        let mut sampler = Sampler::new(22);
+        for (ax, ay) in [(0.2, 0.2), (0.05, 0.5)] {
+            let white = Fresnel::Schlick(Color::new(1.0, 1.0, 1.0));
+            let bsdf =
+                MicrofacetReflection::new(TrowbridgeReitz::new(ax, ay), white, normal, incoming);
+            let n = 400_000;
+            let mut sampled = 0.0;
+            for _ in 0..n {
+                if let Some(direction) = bsdf.sample(&mut sampler) {
+                    let pdf = bsdf.pdf(&direction);
+                    sampled += bsdf.eval(&direction).x() / pdf / n as f64;
+                }
+            }
+            let uniform: f64 = (0..n)
+                .map(|_| bsdf.eval(&random_unit_vector(&mut sampler)).x() * 4.0 * PI)
+                .sum::<f64>()
+                / n as f64;
+            assert!(sampled > 0.8 && sampled <= 1.0, "{}", sampled);
+            assert!(
+                (uniform - sampled).abs() < 0.05,
+                "{} vs {}",
+                uniform,
+                sampled
+            );
+        }
+
+        // Gold reflects red more than blue, and all metals approach white at grazing angles
+        let gold = Fresnel::Conductor {
+            eta: Color::new(0.143, 0.374, 1.442),
+            k: Color::new(3.983, 2.385, 1.603),
+        };
+        let head_on = gold.reflectance(1.0);
+        assert!(head_on.x() > 0.9 && head_on.z() < 0.5, "{}", head_on);
+        assert!(gold.reflectance(0.0).z() > 0.999);
+    }
+
+    #[test]
+    fn rough_dielectric_samples_follow_pdf() {
+        // Entering and leaving glass, including past the critical angle, importance sampling
+        // and uniform sampling agree on the scattered energy
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let mut sampler = Sampler::new(23);
+        for (eta, incoming) in [
+            (1.5, Vec3::new(0.5, -1.0, 0.0)),
+            (1.0 / 1.5, Vec3::new(0.2, -1.0, 0.3)),
+            (1.0 / 1.5, Vec3::new(1.0, -0.5, 0.0)),
+        ] {
+            let bsdf =
+                MicrofacetDielectric::new(TrowbridgeReitz::new(0.3, 0.3), eta, normal, incoming);
+            let n = 400_000;
+            let mut sampled = 0.0;
+            for _ in 0..n {
+                if let Some(direction) = bsdf.sample(&mut sampler) {
+                    sampled += bsdf.eval(&direction).x() / bsdf.pdf(&direction) / n as f64;
+                }
+            }
+            let uniform: f64 = (0..n)
+                .map(|_| bsdf.eval(&random_unit_vector(&mut sampler)).x() * 4.0 * PI)
+                .sum::<f64>()
+                / n as f64;
+            assert!(
+                (uniform - sampled).abs() < 0.03 * sampled,
+                "eta {}: {} vs {}",
+                eta,
+                uniform,
+                sampled
+            );
+        }
+
+        // Nearly smooth glass splits light between the mirror and refracted directions, with
+        // the Fresnel reflectance of the smooth surface. GGX has long tails, so a few samples
+        // stray further.
+        let incoming = unit_vector(Vec3::new(0.4, -1.0, 0.0));
+        let bsdf =
+            MicrofacetDielectric::new(TrowbridgeReitz::new(0.002, 0.002), 1.5, normal, incoming);
+        let mirror = Vec3::new(incoming.x(), -incoming.y(), 0.0);
+        let refracted = refract(&incoming, &normal, 1.0 / 1.5);
+        let n = 100_000;
+        let (mut reflected, mut stray) = (0, 0);
+        for _ in 0..n {
+            let direction = bsdf.sample(&mut sampler).unwrap();
+            let expected = if direction.y() > 0.0 {
+                reflected += 1;
+                mirror
+            } else {
+                refracted
+            };
+            if dot(direction, expected) < 0.999 {
+                stray += 1;
+            }
+        }
+        assert!(stray < n / 100, "{}", stray);
+        let expected = fresnel_dielectric(-incoming.y(), 1.5);
+        assert!((reflected as f64 / n as f64 - expected).abs() < 0.005);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitFn, HitRecord, Hittable},
+    hittable_list::HittableList,
+    ray::Ray,
+    sampler::Sampler,
+};
+
+// Number of centroid buckets evaluated per axis when searching for a split
+const SAH_BUCKETS: usize = 12;
+// Relative cost of visiting an interior node versus intersecting one primitive
+const TRAVERSAL_COST: f64 = 0.125;
+
+type Objects = Vec<Box<dyn Hittable>>;
+
+pub struct BvhNode {
+    left: Box<dyn Hittable>,
+    right: Box<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> Self {
+        let mut objects = list.objects;
+        match objects.len() {
+            0 => Self::from_pair(Box::new(HittableList::new()), Box::new(HittableList::new())),
+            1 => {
+                let only = objects.pop().unwrap();
+                Self::from_pair(only, Box::new(HittableList::new()))
+            }
+            _ => Self::from_objects(objects),
+        }
+    }
+
+    fn from_pair(left: Box<dyn Hittable>, right: Box<dyn Hittable>) -> Self {
+        let bbox = left.bounding_box().union(&right.bounding_box());
+        Self { left, right, bbox }
+    }
+
+    // Splits two or more objects between the children and builds a subtree for each
+    fn from_objects(objects: Objects) -> Self {
+        let bbox = objects
+            .iter()
+            .fold(Aabb::empty(), |b, o| b.union(&o.bounding_box()));
+        let (left, right) = split(objects, &bbox, |o| o.bounding_box());
+        Self {
+            left: build(left),
+            right: build(right),
+            bbox,
+        }
+    }
+}
+
+fn build(mut objects: Objects) -> Box<dyn Hittable> {
+    if objects.len() == 1 {
+        return objects.pop().unwrap();
+    }
+    Box::new(BvhNode::from_objects(objects))
+}
+
+// Partition the objects in two using the surface area heuristic over bucketed centroids.
+// Falls back to halving the list when every centroid lands in the same place. Generic so that
+// meshes can split bare face indices the same way.
+pub(crate) fn split<T>(
+    mut objects: Vec<T>,
+    bbox: &Aabb,
+    bounds: impl Fn(&T) -> Aabb,
+) -> (Vec<T>, Vec<T>) {
+    let boxes: Vec<Aabb> = objects.iter().map(bounds).collect();
+    let centroid_bounds = boxes.iter().fold(Aabb::empty(), |b, o| {
+        b.union(&Aabb::new(o.centroid(), o.centroid()))
+    });
+
+    let mut best: Option<(f64, usize, usize)> = None; // (cost, axis, bucket boundary)
+    for axis in 0..3 {
+        let lo = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - lo;
+        if extent <= 0.0 {
+            continue;
+        }
+        let bucket_of = |b: &Aabb| {
+            let offset = (b.centroid()[axis] - lo) / extent;
+            ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+        };
+
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut bounds = [Aabb::empty(); SAH_BUCKETS];
+        for b in &boxes {
+            let i = bucket_of(b);
+            counts[i] += 1;
+            bounds[i] = bounds[i].union(b);
+        }
+
+        for boundary in 1..SAH_BUCKETS {
+            let (mut left_box, mut left_count) = (Aabb::empty(), 0);
+            let (mut right_box, mut right_count) = (Aabb::empty(), 0);
+            for i in 0..boundary {
+                left_box = left_box.union(&bounds[i]);
+                left_count += counts[i];
+            }
+            for i in boundary..SAH_BUCKETS {
+                right_box = right_box.union(&bounds[i]);
+                right_count += counts[i];
+            }
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (left_box.surface_area() * left_count as f64
+                    + right_box.surface_area() * right_count as f64)
+                    / bbox.surface_area().max(f64::MIN_POSITIVE);
+            if best.is_none_or(|(c, _, _)| cost < c) {
+                best = Some((cost, axis, boundary));
+            }
+        }
+    }
+
+    match best {
+        Some((_, axis, boundary)) => {
+            let lo = centroid_bounds.min[axis];
+            let extent = centroid_bounds.max[axis] - lo;
+            let mut left = Vec::new();
+            let mut right = Vec::new();
+            for (object, b) in objects.into_iter().zip(boxes.iter()) {
+                let offset = (b.centroid()[axis] - lo) / extent;
+                let i = ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1);
+                if i < boundary {
+                    left.push(object);
+                } else {
+                    right.push(object);
+                }
+            }
+            (left, right)
+        }
+        None => {
+            // All centroids coincide, so any split is as good as another
+            let right = objects.split_off(objects.len() / 2);
+            (objects, right)
+        }
+    }
+}
+
+impl BvhNode {
+    // Closest hit in either child, each queried with hit
+    fn closest(
+        &self,
+        hit: HitFn,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return false;
+        }
+        let hit_left = hit(self.left.as_ref(), r, t_min, t_max, rec, sampler);
+        let hit_right = hit(
+            self.right.as_ref(),
+            r,
+            t_min,
+            if hit_left { rec.t } else { t_max },
+            rec,
+            sampler,
+        );
+        hit_left || hit_right
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        self.closest(<dyn Hittable>::hit, r, t_min, t_max, rec, sampler)
+    }
+
+    fn hit_surface(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        self.closest(<dyn Hittable>::hit_surface, r, t_min, t_max, rec, sampler)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> f64 {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return 1.0;
+        }
+        self.left.transmittance(r, t_min, t_max, sampler)
+            * self.right.transmittance(r, t_min, t_max, sampler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        rtweekend::INFINITY,
+        sphere::Sphere,
+        vec3::{unit_vector, Color, Point3, Vec3},
+    };
+
+    fn random_spheres(sampler: &mut Sampler, count: usize) -> Vec<(Point3, f64)> {
+        (0..count)
+            .map(|_| {
+                (
+                    Vec3::random_range(sampler, -10.0, 10.0),
+                    sampler.random_double_range(0.1, 1.0),
+                )
+            })
+            .collect()
+    }
+
+    fn list_of(spheres: &[(Point3, f64)]) -> HittableList {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+        for &(center, radius) in spheres {
+            list.add(Box::new(Sphere::with_values(
+                center,
+                radius,
+                material.clone(),
+            )));
+        }
+        list
+    }
+
+    #[test]
+    fn matches_flat_list() {
+        let mut sampler = Sampler::new(1);
+        let spheres = random_spheres(&mut sampler, 500);
+        let flat = list_of(&spheres);
+        let bvh = BvhNode::new(list_of(&spheres));
+
+        for _ in 0..2000 {
+            let origin = Vec3::random_range(&mut sampler, -15.0, 15.0);
+            let direction = unit_vector(Vec3::random_range(&mut sampler, -1.0, 1.0));
+            let r = Ray::new(origin, direction);
+
+            let mut flat_rec = HitRecord::default();
+            let mut bvh_rec = HitRecord::default();
+            let flat_hit = flat.hit(&r, 0.001, INFINITY, &mut flat_rec, &mut sampler);
+            let bvh_hit = bvh.hit(&r, 0.001, INFINITY, &mut bvh_rec, &mut sampler);
+            assert_eq!(flat_hit, bvh_hit);
+            if flat_hit {
+                assert_eq!(flat_rec.t, bvh_rec.t);
+                assert_eq!(flat_rec.front_face, bvh_rec.front_face);
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_every_object() {
+        let spheres = random_spheres(&mut Sampler::new(2), 100);
+        let bvh = BvhNode::new(list_of(&spheres));
+        let bbox = bvh.bounding_box();
+        for &(center, radius) in &spheres {
+            for a in 0..3 {
+                assert!(bbox.min[a] <= center[a] - radius);
+                assert!(bbox.max[a] >= center[a] + radius);
+            }
+        }
+    }
+}
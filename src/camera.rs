@@ -1,14 +1,15 @@
 use crate::{
-    color::{clamp_color, write_color},
+    bsdf::Bsdf,
     hittable::{HitRecord, Hittable},
-    hittable_list::HittableList,
+    image::{Image, Pixel},
+    material::ScatterRecord,
     ray::Ray,
-    rtweekend::{degrees_to_radians, random_double, INFINITY},
+    rtweekend::{degrees_to_radians, INFINITY},
+    sampler::Sampler,
+    scene::Scene,
     vec3::{cross, random_in_unit_disk, unit_vector, Color, Point3, Vec3},
 };
 
-use std::io::Write;
-
 use rayon::prelude::*;
 
 pub struct Camera {
@@ -28,25 +29,85 @@ pub struct Camera {
     pub defocus_angle: f64,
     pub defocus_disk_u: Vec3,
     pub defocus_disk_v: Vec3,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    pub seed: u64,
+}
+
+// Everything needed to construct a Camera, with defaults for a small, quick preview
+#[derive(Clone, Debug)]
+pub struct CameraSettings {
+    pub max_depth: u32,         // Maximum number of ray bounces into the scene
+    pub aspect_ratio: f64,      // Ratio of image width over height
+    pub image_width: u32,       // Rendered image width in pixel count
+    pub samples_per_pixel: u32, // Count of random samples for each pixel
+    pub vfov: f64,              // Vertical view angle (field of view) in degrees
+    pub lookfrom: Point3,       // Point camera is looking from
+    pub lookat: Point3,         // Point camera is looking at
+    pub vup: Vec3,              // Camera-relative "up" direction
+    pub focus_dist: f64,        // Distance from camera lookfrom point to plane of perfect focus
+    pub defocus_angle: f64,     // Variation angle of rays through each pixel
+    pub shutter_open: f64,      // Time the shutter opens; moving objects start out at time 0
+    pub shutter_close: f64,     // Time the shutter closes; moving objects arrive at time 1
+    pub seed: u64,              // Seed that every random decision in a render derives from
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            max_depth: 10,
+            aspect_ratio: 1.0,
+            image_width: 100,
+            samples_per_pixel: 10,
+            vfov: 90.0,
+            lookfrom: Point3::new(0.0, 0.0, 0.0),
+            lookat: Point3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            focus_dist: 10.0,
+            defocus_angle: 0.0,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+// Weight for a sample drawn with density f_pdf when g_pdf could also have produced it
+// (Veach's power heuristic with an exponent of two)
+fn power_heuristic(f_pdf: f64, g_pdf: f64) -> f64 {
+    let f2 = f_pdf * f_pdf;
+    let g2 = g_pdf * g_pdf;
+    if f2 + g2 == 0.0 {
+        return 0.0;
+    }
+    f2 / (f2 + g2)
 }
 
-fn sample_square() -> Vec3 {
-    Vec3::new(random_double() - 0.5, random_double() - 0.5, 0.)
+fn sample_square(sampler: &mut Sampler) -> Vec3 {
+    Vec3::new(
+        sampler.random_double() - 0.5,
+        sampler.random_double() - 0.5,
+        0.,
+    )
 }
 
 impl Camera {
-    pub fn new(
-        max_depth: u32,
-        aspect_ratio: f64,
-        image_width: u32,
-        samples_per_pixel: u32,
-        vfov: f64,
-        lookfrom: Point3,
-        lookat: Point3,
-        vup: Vec3,
-        focus_dist: f64,
-        defocus_angle: f64,
-    ) -> Self {
+    pub fn new(settings: &CameraSettings) -> Self {
+        let CameraSettings {
+            max_depth,
+            aspect_ratio,
+            image_width,
+            samples_per_pixel,
+            vfov,
+            lookfrom,
+            lookat,
+            vup,
+            focus_dist,
+            defocus_angle,
+            shutter_open,
+            shutter_close,
+            seed,
+        } = *settings;
         let mut image_height = (image_width as f64 / aspect_ratio) as u32;
         image_height = if image_height < 1 { 1 } else { image_height };
 
@@ -59,7 +120,7 @@ impl Camera {
         let viewport_width = (image_width as f64 / image_height as f64) * viewport_height;
 
         // Calculate the u, v, w unit basis vectors for the camera coordinate frame
-        let w = lookfrom - lookat;
+        let w = unit_vector(lookfrom - lookat);
         let u = unit_vector(cross(vup, w));
         let v = cross(w, u);
 
@@ -98,74 +159,562 @@ impl Camera {
             defocus_angle,
             defocus_disk_u,
             defocus_disk_v,
+            shutter_open,
+            shutter_close,
+            seed,
         }
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = random_in_unit_disk();
+    fn defocus_disk_sample(&self, sampler: &mut Sampler) -> Point3 {
+        let p = random_in_unit_disk(sampler);
         self.center + (self.defocus_disk_u * p[0]) + (self.defocus_disk_v * p[1])
     }
 
-    pub fn get_ray(&self, i: f64, j: f64) -> Ray {
+    pub fn get_ray(&self, i: f64, j: f64, sampler: &mut Sampler) -> Ray {
         // Construct a camera ray originating from the origin and directed at a randomly sampled
         // point around the pixel location i, j
-        let offset: Vec3 = sample_square();
+        let offset: Vec3 = sample_square(sampler);
         let pixel_sample = self.pixel00_loc
             + (self.pixel_delta_u * (i + offset.x()))
             + (self.pixel_delta_v * (j + offset.y()));
         let ray_origin = if self.defocus_angle <= 0. {
             self.center
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(sampler)
         };
         let ray_direction = pixel_sample - ray_origin;
-        return Ray::new(ray_origin, ray_direction);
+        let ray_time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + (self.shutter_close - self.shutter_open) * sampler.random_double()
+        } else {
+            self.shutter_open
+        };
+        Ray::with_time(ray_origin, ray_direction, ray_time)
     }
 
-    fn ray_color(&self, r: &Ray, world: &dyn Hittable, depth: u32) -> Color {
-        if depth <= 0 {
+    // emission_weight is the MIS weight for light emitted by whatever r hits. It is below one
+    // when r was sampled from a BSDF in a direction the scene's lights could also have been
+    // sampled in, since direct_light already counted part of that light.
+    fn ray_color(
+        &self,
+        r: &Ray,
+        scene: &Scene,
+        depth: u32,
+        emission_weight: f64,
+        sampler: &mut Sampler,
+    ) -> Color {
+        if depth == 0 {
             return Color::new(0.0, 0.0, 0.0);
         }
         let mut rec = HitRecord::default();
-        if world.hit(r, 0.001, INFINITY, &mut rec) {
-            let mut scattered = Ray::default();
-            let mut attenuation = Color::new(0.0, 0.0, 0.0);
-            if rec
-                .mat_ptr
-                .as_ref()
-                .unwrap()
-                .scatter(r, &rec, &mut attenuation, &mut scattered)
+        if !scene.world.hit(r, 0.001, INFINITY, &mut rec, sampler) {
+            // A background that direct_light samples has already been partly counted
+            if scene.background.is_sampleable() {
+                return scene.background.value(r) * emission_weight;
+            }
+            return scene.background.value(r);
+        }
+        self.shade(r, &rec, scene, depth, emission_weight, sampler)
+    }
+
+    // Light leaving the surface at rec back along r
+    fn shade(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        scene: &Scene,
+        depth: u32,
+        emission_weight: f64,
+        sampler: &mut Sampler,
+    ) -> Color {
+        let material = rec.mat_ptr.as_ref().unwrap();
+        // Shadow rays pass through media, so direct_light never counts what a medium emits
+        let emission_weight = if material.is_volumetric() {
+            1.0
+        } else {
+            emission_weight
+        };
+        let color_from_emission = material.emitted(r, rec) * emission_weight;
+        let bsdf = match material.scatter(r, rec, sampler) {
+            None => return color_from_emission,
+            Some(ScatterRecord::Specular { attenuation, ray }) => {
+                let color_from_scatter =
+                    attenuation * self.ray_color(&ray, scene, depth - 1, 1.0, sampler);
+                return color_from_emission + color_from_scatter;
+            }
+            Some(ScatterRecord::Bsdf(bsdf)) => bsdf,
+        };
+
+        let color_from_lights = self.direct_light(r, rec, bsdf.as_ref(), scene, sampler)
+            + self.delta_lights(r, rec, bsdf.as_ref(), scene, sampler);
+
+        let Some(direction) = bsdf.sample(sampler) else {
+            return color_from_emission + color_from_lights;
+        };
+        let bsdf_pdf = bsdf.pdf(&direction);
+        if bsdf_pdf <= 0.0 {
+            return color_from_emission + color_from_lights;
+        }
+        let light_pdf = scene.light_pdf(&rec.p, &direction);
+        let scattered = Ray::with_time(rec.p, direction, r.time());
+        let color_from_scatter = bsdf.eval(&direction) / bsdf_pdf
+            * self.ray_color(
+                &scattered,
+                scene,
+                depth - 1,
+                power_heuristic(bsdf_pdf, light_pdf),
+                sampler,
+            );
+        color_from_emission + color_from_lights + color_from_scatter
+    }
+
+    // Next-event estimation: light arriving at rec straight from a random point on one of the
+    // scene's lights, or from the environment map, found by casting a shadow ray towards it
+    // and weighted against the chance that sampling the BSDF would have found the same light
+    fn direct_light(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        bsdf: &dyn Bsdf,
+        scene: &Scene,
+        sampler: &mut Sampler,
+    ) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        if !scene.has_lights() {
+            return black;
+        }
+        let direction = scene.sample_light(&rec.p, sampler);
+        let light_pdf = scene.light_pdf(&rec.p, &direction);
+        if light_pdf <= 0.0 {
+            return black;
+        }
+        let f = bsdf.eval(&direction);
+        if f.near_zero() {
+            return black;
+        }
+        // Whatever surface the shadow ray hits first decides what it sees; an occluder emits
+        // nothing. Media it passes through on the way only dim the light.
+        let shadow_ray = Ray::with_time(rec.p, direction, r.time());
+        let mut light_rec = HitRecord::default();
+        let (emitted, t_max) =
+            if scene
+                .world
+                .hit_surface(&shadow_ray, 0.001, INFINITY, &mut light_rec, sampler)
             {
-                return attenuation * self.ray_color(&scattered, world, depth - 1);
+                let material = light_rec.mat_ptr.as_ref().unwrap();
+                (material.emitted(&shadow_ray, &light_rec), light_rec.t)
+            } else if scene.background.is_sampleable() {
+                (scene.background.value(&shadow_ray), INFINITY)
+            } else {
+                return black;
+            };
+        if emitted.near_zero() {
+            return black;
+        }
+        let transmittance = scene
+            .world
+            .transmittance(&shadow_ray, 0.001, t_max, sampler);
+        let weight = power_heuristic(light_pdf, bsdf.pdf(&direction));
+        f * emitted * (transmittance * weight / light_pdf)
+    }
+
+    // Light arriving at rec from every point, spot and directional light. Each reaches rec
+    // from exactly one direction, so it takes a single shadow ray and needs no MIS weight.
+    fn delta_lights(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        bsdf: &dyn Bsdf,
+        scene: &Scene,
+        sampler: &mut Sampler,
+    ) -> Color {
+        let mut total = Color::new(0.0, 0.0, 0.0);
+        for light in scene.lights.objects.iter().filter(|l| l.is_delta_light()) {
+            let Some(sample) = light.illuminate(&rec.p) else {
+                continue;
+            };
+            let f = bsdf.eval(&sample.direction);
+            if f.near_zero() || sample.irradiance.near_zero() {
+                continue;
             }
-            return Color::new(0.0, 0.0, 0.0);
+            let shadow_ray = Ray::with_time(rec.p, sample.direction, r.time());
+            let t_max = sample.distance * (1.0 - 1e-6);
+            if scene.world.hit_surface(
+                &shadow_ray,
+                0.001,
+                t_max,
+                &mut HitRecord::default(),
+                sampler,
+            ) {
+                continue;
+            }
+            let transmittance = scene
+                .world
+                .transmittance(&shadow_ray, 0.001, t_max, sampler);
+            total += f * sample.irradiance * transmittance;
+        }
+        total
+    }
+
+    // Trace one camera ray, also reporting whether it covers the pixel. A miss only does when
+    // the background is visible; otherwise it adds nothing, so color stays premultiplied by
+    // alpha.
+    fn sample_pixel(&self, r: &Ray, scene: &Scene, sampler: &mut Sampler) -> (Color, bool) {
+        let mut rec = HitRecord::default();
+        if !scene.world.hit(r, 0.001, INFINITY, &mut rec, sampler) {
+            if !scene.background_visible {
+                return (Color::new(0.0, 0.0, 0.0), false);
+            }
+            return (scene.background.value(r), true);
         }
-        let unit_direction: Vec3 = unit_vector(r.direction());
-        let t = 0.5 * (unit_direction.y() + 1.0);
-        Color::new(1.0, 1.0, 1.0) * (1.0 - t) + Color::new(0.5, 0.7, 1.0) * t
+        (
+            self.shade(r, &rec, scene, self.max_depth, 1.0, sampler),
+            true,
+        )
     }
 
-    pub fn render(&self, world: &HittableList) {
-        let pixels: Vec<String> = (0..self.image_height)
+    pub fn render(&self, scene: &Scene) -> Image {
+        let pixels: Vec<Pixel> = (0..self.image_height)
             .into_par_iter()
             .flat_map(|j| {
-                let row: Vec<String> = (0..self.image_width)
+                let row: Vec<Pixel> = (0..self.image_width)
                     .into_par_iter()
                     .map(|i| {
                         let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                        for _ in 0..self.samples_per_pixel {
-                            let r = self.get_ray(i as f64, j as f64);
-                            pixel_color += self.ray_color(&r, world, self.max_depth);
+                        let mut hits = 0;
+                        let pixel = j as u64 * self.image_width as u64 + i as u64;
+                        for s in 0..self.samples_per_pixel {
+                            let mut sampler = Sampler::for_sample(self.seed, pixel, s as u64);
+                            let r = self.get_ray(i as f64, j as f64, &mut sampler);
+                            let (color, hit) = self.sample_pixel(&r, scene, &mut sampler);
+                            pixel_color += color;
+                            hits += hit as u32;
+                        }
+                        Pixel {
+                            color: pixel_color * self.pixel_samples_scale,
+                            alpha: hits as f64 * self.pixel_samples_scale,
                         }
-                        let color = clamp_color(pixel_color * self.pixel_samples_scale);
-                        color
                     })
                     .collect();
                 row
             })
             .collect();
-        for p in pixels {
-            let _ = writeln!(&mut std::io::stdout(), "{}", p);
+        Image::from_pixels(
+            self.image_width as usize,
+            self.image_height as usize,
+            pixels,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        aabb::Aabb,
+        background::Background,
+        environment::EnvironmentMap,
+        hittable_list::HittableList,
+        light::{DirectionalLight, PointLight},
+        material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Metal},
+        medium::GridMedium,
+        rtweekend::PI,
+        sphere::Sphere,
+        texture::SolidColor,
+        voxel_grid::VoxelGrid,
+    };
+
+    fn render_with_threads(threads: usize, seed: u64) -> Image {
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::with_values(
+            Point3::new(0., -100.5, -1.),
+            100.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        world.add(Box::new(Sphere::with_values(
+            Point3::new(-0.5, 0., -1.),
+            0.5,
+            Arc::new(Dielectric::new(1.5)),
+        )));
+        world.add(Box::new(Sphere::with_values(
+            Point3::new(0.5, 0., -1.),
+            0.5,
+            Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)),
+        )));
+        let scene = Scene::new(world, HittableList::new(), Background::default());
+        let camera = Camera::new(&CameraSettings {
+            image_width: 16,
+            samples_per_pixel: 4,
+            defocus_angle: 2.0,
+            focus_dist: 1.0,
+            seed,
+            ..CameraSettings::default()
+        });
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| camera.render(&scene))
+    }
+
+    #[test]
+    fn render_is_independent_of_thread_count() {
+        let bits = |image: &Image| -> Vec<u64> {
+            image
+                .pixels()
+                .iter()
+                .flat_map(|p| [p.color.x(), p.color.y(), p.color.z(), p.alpha])
+                .map(f64::to_bits)
+                .collect()
+        };
+        let single = bits(&render_with_threads(1, 7));
+        assert_eq!(single, bits(&render_with_threads(4, 7)));
+        assert_ne!(single, bits(&render_with_threads(1, 8)));
+    }
+
+    fn average(image: &Image) -> f64 {
+        let sum: f64 = image
+            .pixels()
+            .iter()
+            .map(|p| p.color.x() + p.color.y() + p.color.z())
+            .sum();
+        sum / image.pixels().len() as f64
+    }
+
+    #[test]
+    fn view_does_not_depend_on_distance_to_lookat() {
+        // Square pixels, with the viewport focus_dist in front of the camera, however far
+        // lookat is
+        for distance in [1.0, 13.5] {
+            let camera = Camera::new(&CameraSettings {
+                image_width: 4,
+                lookfrom: Point3::new(0., 0., distance),
+                lookat: Point3::new(0., 0., 0.),
+                ..CameraSettings::default()
+            });
+            let (du, dv) = (camera.pixel_delta_u, camera.pixel_delta_v);
+            assert!((du.length() - dv.length()).abs() < 1e-12);
+            let middle = camera.pixel00_loc + (du + dv) * 1.5;
+            assert!((middle.z() - (distance - 10.0)).abs() < 1e-12);
         }
     }
+
+    #[test]
+    fn hidden_background_is_transparent() {
+        // Half the frame is a sphere, the rest background, which only counts towards alpha
+        // when it is shown
+        let render = |visible: bool| {
+            let mut world = HittableList::new();
+            world.add(Box::new(Sphere::with_values(
+                Point3::new(0., -100.5, -1.),
+                100.,
+                Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            )));
+            let mut scene = Scene::new(
+                world,
+                HittableList::new(),
+                Background::Solid(Color::new(1., 1., 1.)),
+            );
+            scene.background_visible = visible;
+            let camera = Camera::new(&CameraSettings {
+                image_width: 4,
+                samples_per_pixel: 4,
+                max_depth: 2,
+                ..CameraSettings::default()
+            });
+            camera.render(&scene)
+        };
+        let top = |image: &Image| image.get(0, 0);
+        assert_eq!(top(&render(true)).alpha, 1.0);
+        assert_eq!(top(&render(true)).color.x(), 1.0);
+        assert_eq!(top(&render(false)).alpha, 0.0);
+        assert_eq!(top(&render(false)).color.x(), 0.0);
+        assert_eq!(render(false).get(0, 3).alpha, 1.0);
+    }
+
+    #[test]
+    fn light_sampling_matches_brute_force() {
+        // A diffuse floor lit only by a sphere light: sampling the light directly must
+        // converge to the same brightness as waiting for bounces to find it
+        let floor = || {
+            Box::new(Sphere::with_values(
+                Point3::new(0., -100.5, -1.),
+                100.,
+                Arc::new(Lambertian::new(Color::new(0.7, 0.7, 0.7))),
+            ))
+        };
+        let light = || {
+            Sphere::with_values(
+                Point3::new(0., 1.5, -1.),
+                0.5,
+                Arc::new(DiffuseLight::new(Color::new(4., 4., 4.))),
+            )
+        };
+        let render = |with_lights: bool, spp: u32| {
+            let mut world = HittableList::new();
+            world.add(floor());
+            world.add(Box::new(light()));
+            let mut lights = HittableList::new();
+            if with_lights {
+                lights.add(Box::new(light()));
+            }
+            let scene = Scene::new(world, lights, Background::None);
+            let camera = Camera::new(&CameraSettings {
+                image_width: 8,
+                samples_per_pixel: spp,
+                max_depth: 4,
+                ..CameraSettings::default()
+            });
+            average(&camera.render(&scene))
+        };
+        let brute_force = render(false, 8000);
+        let sampled = render(true, 1000);
+        assert!(
+            (brute_force - sampled).abs() < 0.03 * brute_force,
+            "{} vs {}",
+            brute_force,
+            sampled
+        );
+    }
+
+    #[test]
+    fn glowing_medium_in_front_of_light_keeps_its_emission() {
+        // Shadow rays pass through media, so light sampling never sees what a glowing medium
+        // around the light emits. Paths that reach it by bouncing must count all of it.
+        let light = || {
+            Sphere::with_values(
+                Point3::new(0., 1.5, -1.),
+                0.5,
+                Arc::new(DiffuseLight::new(Color::new(4., 4., 4.))),
+            )
+        };
+        let render = |with_lights: bool, spp: u32| {
+            let mut world = HittableList::new();
+            world.add(Box::new(Sphere::with_values(
+                Point3::new(0., -100.5, -1.),
+                100.,
+                Arc::new(Lambertian::new(Color::new(0.7, 0.7, 0.7))),
+            )));
+            world.add(Box::new(light()));
+            let glow = GridMedium::new(
+                Aabb::new(Point3::new(-1., 0.8, -2.), Point3::new(1., 2.2, 0.)),
+                Arc::new(VoxelGrid::new([1, 1, 1], 1, vec![1.0])),
+                1.0,
+                Arc::new(Isotropic::new(Color::new(0.5, 0.5, 0.5))),
+            )
+            .with_emission(Arc::new(SolidColor::new(Color::new(4., 4., 4.))));
+            world.add(Box::new(glow));
+            let mut lights = HittableList::new();
+            if with_lights {
+                lights.add(Box::new(light()));
+            }
+            let scene = Scene::new(world, lights, Background::None);
+            let camera = Camera::new(&CameraSettings {
+                image_width: 8,
+                samples_per_pixel: spp,
+                max_depth: 4,
+                vfov: 30.0,
+                lookfrom: Point3::new(0., 0., 1.),
+                lookat: Point3::new(0., -0.5, -1.),
+                ..CameraSettings::default()
+            });
+            average(&camera.render(&scene))
+        };
+        let brute_force = render(false, 4000);
+        let sampled = render(true, 1000);
+        assert!(
+            (brute_force - sampled).abs() < 0.03 * brute_force,
+            "{} vs {}",
+            brute_force,
+            sampled
+        );
+    }
+
+    #[test]
+    fn environment_sampling_matches_solid_background() {
+        // A uniform environment map lights a diffuse sphere exactly like a solid background
+        // of the same color, which is only ever found by BSDF sampling
+        let render = |background: Background, spp: u32| {
+            let mut world = HittableList::new();
+            world.add(Box::new(Sphere::with_values(
+                Point3::new(0., 0., -1.),
+                0.5,
+                Arc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6))),
+            )));
+            let mut scene = Scene::new(world, HittableList::new(), background);
+            scene.background_visible = false;
+            let camera = Camera::new(&CameraSettings {
+                image_width: 8,
+                samples_per_pixel: spp,
+                max_depth: 4,
+                vfov: 60.0,
+                ..CameraSettings::default()
+            });
+            average(&camera.render(&scene))
+        };
+        let sky = Color::new(0.8, 0.9, 1.0);
+        let pixel = Pixel {
+            color: sky,
+            alpha: 1.0,
+        };
+        let map = EnvironmentMap::new(Image::from_pixels(8, 4, vec![pixel; 32]), 30.0, 1.0);
+        let solid = render(Background::Solid(sky), 2000);
+        let sampled = render(Background::Environment(map), 500);
+        assert!(
+            (solid - sampled).abs() < 0.03 * solid,
+            "{} vs {}",
+            solid,
+            sampled
+        );
+    }
+
+    #[test]
+    fn delta_lights_match_analytic_irradiance() {
+        // Looking straight down at a diffuse floor lit by a point light overhead and a
+        // directional light, with no bounces: each adds albedo / pi times its irradiance
+        let render = |blocked: bool| {
+            let mut world = HittableList::new();
+            world.add(Box::new(Sphere::with_values(
+                Point3::new(0., -100., 0.),
+                100.,
+                Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            )));
+            if blocked {
+                // Sits between the camera and the point light, shadowing the floor from it
+                world.add(Box::new(Sphere::with_values(
+                    Point3::new(0., 1.5, 0.),
+                    0.2,
+                    Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+                )));
+            }
+            let mut lights = HittableList::new();
+            lights.add(Box::new(PointLight::new(
+                Point3::new(0., 2., 0.),
+                Color::new(4., 4., 4.),
+            )));
+            lights.add(Box::new(DirectionalLight::new(
+                Vec3::new(1., -1., 0.),
+                Color::new(2., 2., 2.),
+            )));
+            let scene = Scene::new(world, lights, Background::None);
+            let camera = Camera::new(&CameraSettings {
+                image_width: 4,
+                aspect_ratio: 1.0,
+                samples_per_pixel: 4,
+                max_depth: 1,
+                vfov: 1.0,
+                lookfrom: Point3::new(0., 1., 0.),
+                lookat: Point3::new(0., 0., 0.),
+                vup: Vec3::new(0., 0., -1.),
+                ..CameraSettings::default()
+            });
+            average(&camera.render(&scene)) / 3.0
+        };
+        // The directional light arrives at 45 degrees, past the blocker
+        let expected = |irradiance: f64| 0.5 / PI * irradiance;
+        let directional = 2.0 * f64::sqrt(0.5);
+        assert!((render(false) - expected(1.0 + directional)).abs() < 1e-3);
+        assert!((render(true) - expected(directional)).abs() < 1e-3);
+    }
 }
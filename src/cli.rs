@@ -0,0 +1,202 @@
+use std::{fmt, path::PathBuf};
+
+use rust_ray_tracing::camera::CameraSettings;
+
+pub const USAGE: &str = "\
+Usage: rust_ray_tracing render <scene.toml> [options]
+       rust_ray_tracing                 Render the built-in scene to stdout
+
+Options:
+  -o, --output <path>   Write the image to <path>; the format follows the extension:
+                        .ppm, .png, .hdr or .exr (default: ASCII PPM on stdout)
+      --bits <n>        Bits per channel: 8 or 16 for PPM and PNG (default 8),
+                        16 (half) or 32 (float) for OpenEXR (default 16)
+      --spp <n>         Samples per pixel
+      --width <n>       Image width in pixels; the height follows the aspect ratio
+      --depth <n>       Maximum number of bounces per path
+      --seed <n>        Seed for the random number generator
+      --threads <n>     Number of worker threads (default: one per core)
+  -h, --help            Print this message";
+
+#[derive(Debug, PartialEq)]
+pub struct CliError(pub String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RenderOptions {
+    pub output: Option<PathBuf>,
+    pub bits: Option<u8>,
+    pub samples_per_pixel: Option<u32>,
+    pub image_width: Option<u32>,
+    pub max_depth: Option<u32>,
+    pub seed: Option<u64>,
+    pub threads: Option<usize>,
+}
+
+impl RenderOptions {
+    // Command-line values take precedence over whatever the scene asked for
+    pub fn apply(&self, settings: &mut CameraSettings) {
+        if let Some(spp) = self.samples_per_pixel {
+            settings.samples_per_pixel = spp;
+        }
+        if let Some(width) = self.image_width {
+            settings.image_width = width;
+        }
+        if let Some(depth) = self.max_depth {
+            settings.max_depth = depth;
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Demo,
+    Help,
+    Render {
+        scene: PathBuf,
+        options: RenderOptions,
+    },
+}
+
+// Parse the arguments that follow the program name
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, CliError> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        None => return Ok(Command::Demo),
+        Some("-h") | Some("--help") => return Ok(Command::Help),
+        Some("render") => {}
+        Some(other) => {
+            return Err(CliError(format!(
+                "unknown command `{}`, expected `render`",
+                other
+            )))
+        }
+    }
+
+    let mut scene = None;
+    let mut options = RenderOptions::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| CliError(format!("{} needs a value", name)))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => options.output = Some(PathBuf::from(value(&arg)?)),
+            "--bits" => {
+                let text = value(&arg)?;
+                options.bits = Some(
+                    text.parse()
+                        .ok()
+                        .filter(|b| [8, 16, 32].contains(b))
+                        .ok_or_else(|| {
+                            CliError(format!("--bits expects 8, 16 or 32, got `{}`", text))
+                        })?,
+                );
+            }
+            "--spp" => options.samples_per_pixel = Some(positive(&arg, &value(&arg)?)?),
+            "--width" => options.image_width = Some(positive(&arg, &value(&arg)?)?),
+            "--depth" => options.max_depth = Some(positive(&arg, &value(&arg)?)?),
+            "--seed" => {
+                let text = value(&arg)?;
+                options.seed = Some(text.parse().map_err(|_| {
+                    CliError(format!(
+                        "--seed expects a non-negative integer, got `{}`",
+                        text
+                    ))
+                })?);
+            }
+            "--threads" => options.threads = Some(positive::<usize>(&arg, &value(&arg)?)?),
+            flag if flag.starts_with('-') => {
+                return Err(CliError(format!("unknown option `{}`", flag)))
+            }
+            path => {
+                if scene.is_some() {
+                    return Err(CliError(format!("unexpected argument `{}`", path)));
+                }
+                scene = Some(PathBuf::from(path));
+            }
+        }
+    }
+
+    let scene = scene.ok_or_else(|| CliError(String::from("missing scene file")))?;
+    Ok(Command::Render { scene, options })
+}
+
+fn positive<T>(name: &str, text: &str) -> Result<T, CliError>
+where
+    T: std::str::FromStr + PartialOrd + Default,
+{
+    text.parse::<T>()
+        .ok()
+        .filter(|n| *n > T::default())
+        .ok_or_else(|| {
+            CliError(format!(
+                "{} expects a positive integer, got `{}`",
+                name, text
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Command, CliError> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_render_options() {
+        let command = parse(
+            "render scene.toml -o out.ppm --spp 500 --width 1920 --depth 50 --seed 42 --threads 8",
+        )
+        .unwrap();
+        assert_eq!(
+            command,
+            Command::Render {
+                scene: PathBuf::from("scene.toml"),
+                options: RenderOptions {
+                    output: Some(PathBuf::from("out.ppm")),
+                    bits: None,
+                    samples_per_pixel: Some(500),
+                    image_width: Some(1920),
+                    max_depth: Some(50),
+                    seed: Some(42),
+                    threads: Some(8),
+                },
+            }
+        );
+        assert_eq!(parse("").unwrap(), Command::Demo);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert_eq!(
+            parse("render scene.toml --spp 0").unwrap_err().to_string(),
+            "--spp expects a positive integer, got `0`"
+        );
+        assert_eq!(
+            parse("render scene.toml --width").unwrap_err().to_string(),
+            "--width needs a value"
+        );
+        assert_eq!(
+            parse("render --fast scene.toml").unwrap_err().to_string(),
+            "unknown option `--fast`"
+        );
+        assert_eq!(
+            parse("render").unwrap_err().to_string(),
+            "missing scene file"
+        );
+    }
+}
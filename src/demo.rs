@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use crate::{
+    background::Background,
+    bvh::BvhNode,
+    camera::CameraSettings,
+    hittable_list::HittableList,
+    material::{Dielectric, Lambertian, Material, Metal},
+    sampler::Sampler,
+    scene::Scene,
+    sphere::Sphere,
+    vec3::{Color, Point3, Vec3},
+};
+
+// The spheres scene used before scene files existed
+pub fn demo_scene() -> (Scene, CameraSettings) {
+    // World
+    let mut world = HittableList::new();
+    let mut sampler = Sampler::new(0);
+
+    // let material_ground = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    // world.add(Box::new(Sphere::with_values(
+    //     Point3::new(-4.0, -1000., 0.0),
+    //     1000.0,
+    //     material_ground.clone(),
+    // )));
+
+    let material_test = Arc::new(Lambertian::new(Color::new(1., 0., 0.)));
+    world.add(Box::new(Sphere::with_values(
+        Point3::new(0., 0., -3.),
+        1.,
+        material_test.clone(),
+    )));
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = sampler.random_double();
+            let center = Point3::new(
+                a as f64 + 0.9 * sampler.random_double(),
+                0.2,
+                b as f64 + 0.9 * sampler.random_double(),
+            );
+            if (center - Point3::new(4., 0.2, 0.)).length() > 0.9 {
+                let sphere_material: Arc<dyn Material>;
+                if choose_mat < 0.8 {
+                    let albedo = Color::random(&mut sampler) * Color::random(&mut sampler);
+                    sphere_material = Arc::new(Lambertian::new(albedo));
+                } else if choose_mat < 0.95 {
+                    let albedo = Color::random_range(&mut sampler, 0.5, 1.);
+                    let fuzz = sampler.random_double_range(0., 0.5);
+                    sphere_material = Arc::new(Metal::new(albedo, fuzz));
+                } else {
+                    sphere_material = Arc::new(Dielectric::new(1.5));
+                }
+                world.add(Box::new(Sphere::with_values(center, 0.2, sphere_material)));
+            }
+        }
+    }
+    // Camera
+
+    let material_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
+    let material_center = Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.3)));
+    let material_left = Arc::new(Dielectric::new(1.5));
+    let material_bubble = Arc::new(Dielectric::new(1.00 / 1.5));
+    let material_right = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 1.0));
+
+    world.add(Box::new(Sphere::with_values(
+        Point3::new(0.0, -100.5, -1.0),
+        100.0,
+        material_ground.clone(),
+    )));
+    world.add(Box::new(Sphere::with_values(
+        Point3::new(0.0, 0.0, -1.0),
+        0.5,
+        material_center.clone(),
+    )));
+    world.add(Box::new(Sphere::with_values(
+        Point3::new(-1.0, 0.0, -1.0),
+        0.5,
+        material_left.clone(),
+    )));
+    world.add(Box::new(Sphere::with_values(
+        Point3::new(-1.0, 0.0, -1.0),
+        0.4,
+        material_bubble.clone(),
+    )));
+    world.add(Box::new(Sphere::with_values(
+        Point3::new(1.0, 0.0, -1.0),
+        0.5,
+        material_right.clone(),
+    )));
+    let world = HittableList::with_object(Box::new(BvhNode::new(world)));
+    let scene = Scene::new(world, HittableList::new(), Background::default());
+
+    let settings = CameraSettings {
+        max_depth: 50,
+        aspect_ratio: 16. / 9.,
+        image_width: 400,
+        samples_per_pixel: 50,
+        vfov: 90.,
+        lookfrom: Point3::new(0., 0., 0.),
+        lookat: Point3::new(0., 0., -1.),
+        vup: Vec3::new(0., 1., 0.),
+        focus_dist: 1.,
+        defocus_angle: 0.6,
+        shutter_open: 0.,
+        shutter_close: 1.,
+        seed: 0,
+    };
+    (scene, settings)
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{planar_pdf, HitRecord, Hittable},
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    rtweekend::{INFINITY, PI},
+    sampler::Sampler,
+    vec3::{dot, Point3, Vec3},
+};
+
+// Flat circle facing along normal. u is the angle around the center and v the distance from
+// it, both scaled to [0, 1].
+#[derive(Clone)]
+pub struct Disk {
+    center: Point3,
+    radius: f64,
+    uvw: Onb, // w is the unit normal
+    mat_ptr: Option<Arc<dyn Material>>,
+}
+
+impl Disk {
+    pub fn with_values(center: Point3, normal: Vec3, radius: f64, m: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            radius,
+            uvw: Onb::build_from_w(normal),
+            mat_ptr: Some(m),
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord, _: &mut Sampler) -> bool {
+        let normal = self.uvw.w();
+        let denom = dot(normal, r.direction());
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+        let t = dot(normal, self.center - r.origin()) / denom;
+        if t < t_min || t_max < t {
+            return false;
+        }
+        let p = r.at(t);
+        let offset = p - self.center;
+        let distance_squared = offset.length_squared();
+        if distance_squared > self.radius * self.radius {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        let phi = f64::atan2(dot(offset, self.uvw.v()), dot(offset, self.uvw.u()));
+        rec.u = (phi + PI) / (2.0 * PI);
+        rec.tangent = self.uvw.v() * phi.cos() - self.uvw.u() * phi.sin();
+        rec.v = distance_squared.sqrt() / self.radius;
+        rec.set_face_normal(r, &normal);
+        rec.mat_ptr = self.mat_ptr.clone();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // Extent along each axis is the radius scaled by the sine of the normal's angle to it
+        let n = self.uvw.w();
+        let extent = Vec3::new(
+            self.radius * f64::sqrt((1.0 - n.x() * n.x()).max(0.0)),
+            self.radius * f64::sqrt((1.0 - n.y() * n.y()).max(0.0)),
+            self.radius * f64::sqrt((1.0 - n.z() * n.z()).max(0.0)),
+        );
+        Aabb::new(self.center - extent, self.center + extent).pad(1e-4)
+    }
+
+    // Points are sampled uniformly by area
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let r = Ray::new(*origin, *direction);
+        let mut rec = HitRecord::default();
+        if !self.hit(&r, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)) {
+            return 0.0;
+        }
+        let area = PI * self.radius * self.radius;
+        planar_pdf(&r, rec.t, &self.uvw.w(), area)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let radius = self.radius * sampler.random_double().sqrt();
+        let phi = 2.0 * PI * sampler.random_double();
+        let p = self.center
+            + self.uvw.u() * (radius * f64::cos(phi))
+            + self.uvw.v() * (radius * f64::sin(phi));
+        p - *origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, vec3::Color};
+
+    #[test]
+    fn hit_reports_uv_and_face() {
+        let disk = Disk::with_values(
+            Point3::new(0., 1., 0.),
+            Vec3::new(0., 1., 0.),
+            2.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let mut rec = HitRecord::default();
+        let down = Ray::new(Point3::new(1., 3., 0.), Vec3::new(0., -1., 0.));
+        assert!(disk.hit(&down, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+        assert!((0.0..=1.0).contains(&rec.u));
+        assert!(rec.front_face);
+        assert!((rec.normal.y() - 1.0).abs() < 1e-12);
+
+        let up = Ray::new(Point3::new(1., -3., 0.), Vec3::new(0., 1., 0.));
+        assert!(disk.hit(&up, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+        assert!(!rec.front_face);
+        assert!((rec.normal.y() + 1.0).abs() < 1e-12);
+
+        let outside = Ray::new(Point3::new(2.5, 3., 0.), Vec3::new(0., -1., 0.));
+        assert!(!disk.hit(&outside, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+
+        let bbox = disk.bounding_box();
+        assert!((bbox.max.x() - 2.0).abs() < 1e-9 && (bbox.min.z() + 2.0).abs() < 1e-9);
+    }
+}
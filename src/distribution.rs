@@ -0,0 +1,133 @@
+// Piecewise-constant distribution over [0, 1), with one step per entry of func
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>, // func.len() + 1 entries, from 0 up to 1
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        assert!(!func.is_empty(), "a distribution needs at least one step");
+        let n = func.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for (i, f) in func.iter().enumerate() {
+            cdf.push(cdf[i] + f.max(0.0) / n as f64);
+        }
+        let integral = cdf[n];
+        if integral > 0.0 {
+            for c in &mut cdf {
+                *c /= integral;
+            }
+        } else {
+            // Nothing to prefer, so fall back to uniform
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    // Average of func over [0, 1)
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // Density at x, relative to the uniform distribution on [0, 1)
+    pub fn pdf(&self, x: f64) -> f64 {
+        let i = ((x * self.count() as f64) as usize).min(self.count() - 1);
+        self.pdf_of(i)
+    }
+
+    fn pdf_of(&self, i: usize) -> f64 {
+        (self.cdf[i + 1] - self.cdf[i]) * self.count() as f64
+    }
+
+    // Map a uniform u in [0, 1) to x in [0, 1) following the distribution, returning x, its
+    // density and the index of the step it landed in
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // Last step whose cdf lies at or below u, skipping empty steps
+        let i = self.cdf.partition_point(|&c| c <= u).clamp(1, self.count()) - 1;
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let offset = if width > 0.0 {
+            (u - self.cdf[i]) / width
+        } else {
+            0.0
+        };
+        let x = ((i as f64 + offset) / self.count() as f64).min(1.0 - f64::EPSILON);
+        (x, self.pdf_of(i), i)
+    }
+}
+
+// Piecewise-constant distribution over [0, 1)^2, given as rows of func. Picks a row from the
+// marginal distribution of row sums, then a column within it.
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+        let rows: Vec<Distribution1D> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|r| r.integral()).collect());
+        Self { rows, marginal }
+    }
+
+    // A point (u, v), with u along rows and v across them, and its density
+    pub fn sample(&self, u1: f64, u2: f64) -> ((f64, f64), f64) {
+        let (v, v_pdf, row) = self.marginal.sample(u2);
+        let (u, u_pdf, _) = self.rows[row].sample(u1);
+        ((u, v), u_pdf * v_pdf)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let height = self.marginal.count();
+        let row = ((v * height as f64) as usize).min(height - 1);
+        self.marginal.pdf(v) * self.rows[row].pdf(u)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::Sampler;
+
+    #[test]
+    fn samples_match_pdf() {
+        // Zero steps are never picked, and sample and pdf agree on the density
+        let d = Distribution1D::new(vec![1.0, 0.0, 3.0, 0.0]);
+        let mut sampler = Sampler::new(9);
+        let mut counts = [0; 4];
+        for _ in 0..40_000 {
+            let (x, pdf, i) = d.sample(sampler.random_double());
+            assert_eq!(pdf, d.pdf(x));
+            assert_eq!(i, (x * 4.0) as usize);
+            counts[i] += 1;
+        }
+        assert_eq!(counts[1] + counts[3], 0);
+        assert!((counts[2] as f64 / counts[0] as f64 - 3.0).abs() < 0.15);
+        assert_eq!(d.pdf(0.6), 3.0);
+
+        let func = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+        let d2 = Distribution2D::new(&func, 3, 2);
+        for _ in 0..1000 {
+            let ((u, v), pdf) = d2.sample(sampler.random_double(), sampler.random_double());
+            assert!((pdf - d2.pdf(u, v)).abs() < 1e-12);
+        }
+        // Density is proportional to func and averages to one
+        assert!((d2.pdf(0.9, 0.9) - 5.0 / 2.5).abs() < 1e-12);
+        assert_eq!(d2.pdf(0.1, 0.1), 0.0);
+    }
+}
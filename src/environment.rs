@@ -0,0 +1,142 @@
+use std::{io, path::Path};
+
+use crate::{
+    color::luminance,
+    distribution::Distribution2D,
+    image::Image,
+    image_io::load_hdr,
+    rtweekend::PI,
+    sampler::Sampler,
+    vec3::{unit_vector, Color, Vec3},
+};
+
+// Light from every direction, read from an equirectangular (latitude-longitude) image. The
+// top row looks straight up, and the middle of the image looks down -z before rotation.
+pub struct EnvironmentMap {
+    image: Image,
+    rotation: f64,  // Turn about +y, as a fraction of a full turn
+    intensity: f64, // Multiplier on every pixel
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    // rotation is in degrees about the vertical axis
+    pub fn new(image: Image, rotation: f64, intensity: f64) -> Self {
+        let (width, height) = (image.width(), image.height());
+        // Pixels near the poles cover less solid angle, in proportion to sin(theta)
+        let weights: Vec<f64> = image
+            .pixels()
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let sin_theta = f64::sin(PI * ((i / width) as f64 + 0.5) / height as f64);
+                luminance(p.color) * sin_theta
+            })
+            .collect();
+        Self {
+            distribution: Distribution2D::new(&weights, width, height),
+            image,
+            rotation: rotation / 360.0,
+            intensity,
+        }
+    }
+
+    pub fn load(path: &Path, rotation: f64, intensity: f64) -> io::Result<Self> {
+        Ok(Self::new(load_hdr(path)?, rotation, intensity))
+    }
+
+    pub fn value(&self, direction: &Vec3) -> Color {
+        let (u, v) = self.uv_of(direction);
+        let (width, height) = (self.image.width(), self.image.height());
+        let i = ((u * width as f64) as usize).min(width - 1);
+        let j = ((v * height as f64) as usize).min(height - 1);
+        self.image.pixels()[j * width + i].color * self.intensity
+    }
+
+    // Density over solid angle with which random picks direction
+    pub fn pdf_value(&self, direction: &Vec3) -> f64 {
+        let (u, v) = self.uv_of(direction);
+        let sin_theta = f64::sin(PI * v);
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        // (u, v) spans 2pi by pi radians, and a patch of it at theta covers sin(theta) as
+        // much solid angle as its area
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    // Unit direction, favouring bright parts of the map
+    pub fn random(&self, sampler: &mut Sampler) -> Vec3 {
+        let ((u, v), _) = self
+            .distribution
+            .sample(sampler.random_double(), sampler.random_double());
+        self.direction_at(u, v)
+    }
+
+    fn uv_of(&self, direction: &Vec3) -> (f64, f64) {
+        let d = unit_vector(*direction);
+        let theta = d.y().clamp(-1.0, 1.0).acos();
+        let phi = f64::atan2(d.x(), -d.z());
+        let u = 0.5 + phi / (2.0 * PI) - self.rotation;
+        (u - u.floor(), theta / PI)
+    }
+
+    fn direction_at(&self, u: f64, v: f64) -> Vec3 {
+        let theta = PI * v;
+        let phi = 2.0 * PI * (u + self.rotation - 0.5);
+        Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image::Pixel, vec3::random_unit_vector};
+
+    #[test]
+    fn sampling_matches_pdf() {
+        // A dim map with one bright pixel, turned a quarter of the way round
+        let (width, height) = (16, 8);
+        let mut pixels = vec![
+            Pixel {
+                color: Color::new(0.1, 0.1, 0.1),
+                alpha: 1.0,
+            };
+            width * height
+        ];
+        pixels[2 * width + 5].color = Color::new(50.0, 40.0, 30.0);
+        let map = EnvironmentMap::new(Image::from_pixels(width, height, pixels), 90.0, 2.0);
+        let mut sampler = Sampler::new(11);
+
+        // Directions map to the pixel they came from, and the pdf integrates to one
+        for _ in 0..100 {
+            let direction = random_unit_vector(&mut sampler);
+            let (u, v) = map.uv_of(&direction);
+            let back = map.direction_at(u, v);
+            assert!((back - direction).length() < 1e-9);
+        }
+        let n = 400_000;
+        let total: f64 = (0..n)
+            .map(|_| map.pdf_value(&random_unit_vector(&mut sampler)) * 4.0 * PI)
+            .sum();
+        assert!(
+            (total / n as f64 - 1.0).abs() < 0.03,
+            "{}",
+            total / n as f64
+        );
+
+        // Most samples head for the bright pixel
+        let bright = (0..1000)
+            .filter(|_| map.value(&map.random(&mut sampler)).x() > 1.0)
+            .count();
+        assert!(bright > 700, "{}", bright);
+        assert_eq!(
+            map.value(&map.direction_at(5.5 / 16.0, 2.5 / 8.0)).x(),
+            100.0
+        );
+    }
+}
@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
 use crate::{
+    aabb::Aabb,
     material::Material,
     ray::Ray,
-    vec3::{dot, Point3, Vec3},
+    sampler::Sampler,
+    vec3::{dot, Color, Point3, Vec3},
 };
 
 #[derive(Clone, Default)]
@@ -12,6 +14,12 @@ pub struct HitRecord {
     pub normal: Vec3,
     pub mat_ptr: Option<Arc<dyn Material>>,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
+    pub barycentric: Vec3, // Weights of a triangle's three vertices at p
+    // Direction along the surface in which u increases, not necessarily of unit length. It
+    // orients anisotropic materials, and is zero where the shape has no such direction.
+    pub tangent: Vec3,
     pub front_face: bool,
 }
 
@@ -26,6 +34,129 @@ impl HitRecord {
     }
 }
 
-pub trait Hittable: Sync {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
+// How a light with no surface, such as a point light, reaches a point
+pub struct LightSample {
+    pub direction: Vec3, // Unit vector from the point towards the light
+    pub distance: f64,   // Infinite for lights that are infinitely far away
+    // Irradiance the light delivers on a surface facing it, so the light reflected towards
+    // the viewer is this times the BSDF times the cosine term
+    pub irradiance: Color,
+}
+
+// Density over solid angle, seen from r's origin, of a point spread uniformly over a flat shape
+// with the given normal and area, where r reaches the shape at parameter t
+pub fn planar_pdf(r: &Ray, t: f64, normal: &Vec3, area: f64) -> f64 {
+    let direction = r.direction();
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = (dot(direction, *normal) / (direction.length() * normal.length())).abs();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+// Either Hittable::hit or Hittable::hit_surface, for containers that forward both the same way
+pub type HitFn =
+    fn(&(dyn Hittable + 'static), &Ray, f64, f64, &mut HitRecord, &mut Sampler) -> bool;
+
+pub trait Hittable: Send + Sync {
+    fn hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool;
+    fn bounding_box(&self) -> Aabb;
+
+    // Density, over solid angle as seen from origin, with which `random` picks direction.
+    // Shapes that can't be sampled report zero and so are never used as lights.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // Direction from origin towards a random point on the shape
+    fn random(&self, _origin: &Point3, _sampler: &mut Sampler) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // Lights with no surface, which rays never hit, return true. They light the scene only
+    // through illuminate and are skipped by pdf_value and random.
+    fn is_delta_light(&self) -> bool {
+        false
+    }
+
+    fn illuminate(&self, _p: &Point3) -> Option<LightSample> {
+        None
+    }
+
+    // Like hit, but passes through participating media as if they weren't there. Shadow rays
+    // find what blocks the light this way and leave the media to transmittance.
+    fn hit_surface(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        self.hit(r, t_min, t_max, rec, sampler)
+    }
+
+    // Fraction of light that participating media let through along r between t_min and
+    // t_max. Surfaces don't attenuate anything here; they block light by being hit.
+    fn transmittance(&self, _r: &Ray, _t_min: f64, _t_max: f64, _sampler: &mut Sampler) -> f64 {
+        1.0
+    }
+}
+
+// Lets one object be shared, e.g. between several instances or between the world and the
+// scene's lights
+impl Hittable for Arc<dyn Hittable> {
+    fn hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        self.as_ref().hit(r, t_min, t_max, rec, sampler)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.as_ref().bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.as_ref().pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        self.as_ref().random(origin, sampler)
+    }
+
+    fn is_delta_light(&self) -> bool {
+        self.as_ref().is_delta_light()
+    }
+
+    fn illuminate(&self, p: &Point3) -> Option<LightSample> {
+        self.as_ref().illuminate(p)
+    }
+
+    fn hit_surface(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        self.as_ref().hit_surface(r, t_min, t_max, rec, sampler)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> f64 {
+        self.as_ref().transmittance(r, t_min, t_max, sampler)
+    }
 }
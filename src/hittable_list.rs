@@ -1,9 +1,12 @@
 use crate::{
-    hittable::{HitRecord, Hittable},
+    aabb::Aabb,
+    hittable::{HitFn, HitRecord, Hittable},
     ray::Ray,
+    sampler::Sampler,
     vec3::{Point3, Vec3},
 };
 
+#[derive(Default)]
 pub struct HittableList {
     pub objects: Vec<Box<dyn Hittable>>,
 }
@@ -16,9 +19,9 @@ impl HittableList {
     }
 
     pub fn with_object(object: Box<dyn Hittable>) -> Self {
-        let mut objects = Vec::new();
-        objects.push(object);
-        Self { objects }
+        Self {
+            objects: vec![object],
+        }
     }
 
     pub fn clear(&mut self) {
@@ -28,21 +31,37 @@ impl HittableList {
     pub fn add(&mut self, object: Box<dyn Hittable>) {
         self.objects.push(object);
     }
-}
 
-impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord {
-            p: Point3::new(0.0, 0.0, 0.0),
-            normal: Vec3::new(0.0, 0.0, 0.0),
-            t: 0.0,
-            front_face: false,
-            mat_ptr: None,
-        };
+    // Number of objects pdf_value and random choose between
+    pub fn sampleable_count(&self) -> usize {
+        self.objects
+            .iter()
+            .filter(|object| !object.is_delta_light())
+            .count()
+    }
+
+    // Closest hit among the objects, each queried with hit
+    fn closest(
+        &self,
+        hit: HitFn,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        let mut temp_rec = HitRecord::default();
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
         for object in &self.objects {
-            if object.hit(r, t_min, closest_so_far, &mut temp_rec) {
+            if hit(
+                object.as_ref(),
+                r,
+                t_min,
+                closest_so_far,
+                &mut temp_rec,
+                sampler,
+            ) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
                 *rec = temp_rec.clone();
@@ -51,3 +70,70 @@ impl Hittable for HittableList {
         hit_anything
     }
 }
+
+impl Hittable for HittableList {
+    fn hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        self.closest(<dyn Hittable>::hit, r, t_min, t_max, rec, sampler)
+    }
+
+    fn hit_surface(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        self.closest(<dyn Hittable>::hit_surface, r, t_min, t_max, rec, sampler)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::empty(), |bbox, object| {
+            bbox.union(&object.bounding_box())
+        })
+    }
+
+    // Sampling picks one object uniformly, so the density is the average over all of them.
+    // Delta lights can't be picked, since no direction has any chance of reaching them.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let count = self.sampleable_count();
+        if count == 0 {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .objects
+            .iter()
+            .filter(|object| !object.is_delta_light())
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+        sum / count as f64
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let count = self.sampleable_count();
+        let index = ((sampler.random_double() * count as f64) as usize).min(count.max(1) - 1);
+        match self
+            .objects
+            .iter()
+            .filter(|object| !object.is_delta_light())
+            .nth(index)
+        {
+            Some(object) => object.random(origin, sampler),
+            None => Vec3::new(1.0, 0.0, 0.0),
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> f64 {
+        self.objects
+            .iter()
+            .map(|object| object.transmittance(r, t_min, t_max, sampler))
+            .product()
+    }
+}
@@ -0,0 +1,58 @@
+use crate::vec3::Color;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Pixel {
+    pub color: Color, // Linear radiance, not clamped or gamma corrected
+    pub alpha: f64,   // Fraction of camera rays that hit geometry or a visible background
+}
+
+// Floating point framebuffer, stored row-major from the top-left corner
+#[derive(Clone, Debug)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Pixel>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Pixel::default(); width * height],
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Pixel>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count must match size");
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Pixel {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, pixel: Pixel) {
+        self.pixels[y * self.width + x] = pixel;
+    }
+
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Pixel]> {
+        self.pixels.chunks(self.width.max(1))
+    }
+}
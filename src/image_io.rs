@@ -0,0 +1,521 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    color::clamp_color,
+    image::{Image, Pixel},
+    vec3::Color,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    PpmAscii,         // P3 text, 8 bits per channel
+    Ppm { bits: u8 }, // P6 binary, 8 or 16 bits per channel
+    Png { bits: u8 }, // 8 or 16 bits per channel
+    Hdr,              // Radiance RGBE
+    Exr { bits: u8 }, // Uncompressed OpenEXR RGBA, 16 (half) or 32 (float) bit samples
+}
+
+impl ImageFormat {
+    // Pick the format from the file extension, with bits overriding the format's default
+    // sample size where the format offers a choice
+    pub fn from_path(path: &Path, bits: Option<u8>) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let check = |format: &str, allowed: &[u8]| -> Result<u8, String> {
+            let bits = bits.unwrap_or(allowed[0]);
+            if allowed.contains(&bits) {
+                Ok(bits)
+            } else {
+                Err(format!(
+                    "{} output does not support {}-bit samples",
+                    format, bits
+                ))
+            }
+        };
+        match extension.as_deref() {
+            Some("ppm") => Ok(ImageFormat::Ppm {
+                bits: check("PPM", &[8, 16])?,
+            }),
+            Some("png") => Ok(ImageFormat::Png {
+                bits: check("PNG", &[8, 16])?,
+            }),
+            Some("hdr") => {
+                check("Radiance HDR", &[32])?;
+                Ok(ImageFormat::Hdr)
+            }
+            Some("exr") => Ok(ImageFormat::Exr {
+                bits: check("OpenEXR", &[16, 32])?,
+            }),
+            _ => Err(format!(
+                "{}: unsupported output format (supported: .ppm, .png, .hdr, .exr)",
+                path.display()
+            )),
+        }
+    }
+}
+
+pub fn save_image(image: &Image, path: &Path, format: ImageFormat) -> io::Result<()> {
+    let file = File::create(path)?;
+    let mut out = BufWriter::new(file);
+    write_image(image, format, &mut out)?;
+    out.flush()
+}
+
+pub fn write_image(image: &Image, format: ImageFormat, out: &mut dyn Write) -> io::Result<()> {
+    match format {
+        ImageFormat::PpmAscii => write_ppm_ascii(image, out),
+        ImageFormat::Ppm { bits } => write_ppm(image, bits, out),
+        ImageFormat::Png { bits } => write_png(image, bits, out),
+        ImageFormat::Hdr => write_hdr(image, out),
+        ImageFormat::Exr { bits } => write_exr(image, bits == 16, out),
+    }
+}
+
+// Display-referred formats get the same gamma 2 encoding as color::clamp_color
+fn encode_8bit(value: f64) -> u8 {
+    (256.0 * value.sqrt().clamp(0.0, 0.999)) as u8
+}
+
+fn encode_16bit(value: f64) -> u16 {
+    (65536.0 * value.sqrt().clamp(0.0, 0.99999)) as u16
+}
+
+// Gamma-encoded RGB samples, big-endian as both PPM and PNG expect
+fn display_samples(image: &Image, bits: u8) -> Vec<u8> {
+    let mut data = Vec::with_capacity(image.pixels().len() * 3 * (bits as usize / 8));
+    for pixel in image.pixels() {
+        for c in 0..3 {
+            if bits == 16 {
+                data.extend_from_slice(&encode_16bit(pixel.color[c]).to_be_bytes());
+            } else {
+                data.push(encode_8bit(pixel.color[c]));
+            }
+        }
+    }
+    data
+}
+
+fn write_ppm_ascii(image: &Image, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "P3\n{} {}\n255", image.width(), image.height())?;
+    for pixel in image.pixels() {
+        writeln!(out, "{}", clamp_color(pixel.color))?;
+    }
+    Ok(())
+}
+
+fn write_ppm(image: &Image, bits: u8, out: &mut dyn Write) -> io::Result<()> {
+    let max_value = if bits == 16 { 65535 } else { 255 };
+    write!(
+        out,
+        "P6\n{} {}\n{}\n",
+        image.width(),
+        image.height(),
+        max_value
+    )?;
+    out.write_all(&display_samples(image, bits))
+}
+
+fn write_png(image: &Image, bits: u8, out: &mut dyn Write) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(out, image.width() as u32, image.height() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(if bits == 16 {
+        png::BitDepth::Sixteen
+    } else {
+        png::BitDepth::Eight
+    });
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(&display_samples(image, bits))
+        .map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+// Shared-exponent encoding: three 8-bit mantissas scaled by 2^(e - 128)
+fn to_rgbe(color: Color) -> [u8; 4] {
+    let v = color.x().max(color.y()).max(color.z());
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let e = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(e);
+    let channel = |c: f64| (c.max(0.0) * scale).min(255.0) as u8;
+    [
+        channel(color.x()),
+        channel(color.y()),
+        channel(color.z()),
+        (e + 128).clamp(0, 255) as u8,
+    ]
+}
+
+fn write_hdr(image: &Image, out: &mut dyn Write) -> io::Result<()> {
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        image.height(),
+        image.width()
+    )?;
+    // Flat scanlines are always valid. They can't be mistaken for run-length encoded ones,
+    // because a (2, 2) prefix leaves blue as the largest channel, and so at least 128.
+    let mut data = Vec::with_capacity(image.pixels().len() * 4);
+    for pixel in image.pixels() {
+        data.extend_from_slice(&to_rgbe(pixel.color));
+    }
+    out.write_all(&data)
+}
+
+fn from_rgbe(rgbe: &[u8]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - 136);
+    Color::new(
+        rgbe[0] as f64 * scale,
+        rgbe[1] as f64 * scale,
+        rgbe[2] as f64 * scale,
+    )
+}
+
+// Read a Radiance HDR file, e.g. an environment map, as linear radiance
+pub fn load_hdr(path: &Path) -> io::Result<Image> {
+    decode_hdr(&fs::read(path)?)
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// Handles flat and run-length encoded scanlines in the usual top-to-bottom orientation
+fn decode_hdr(bytes: &[u8]) -> io::Result<Image> {
+    let mut pos = 0;
+    let mut next_line = || -> io::Result<&str> {
+        let rest = &bytes[pos.min(bytes.len())..];
+        let end = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid_data("unexpected end of HDR header"))?;
+        pos += end + 1;
+        std::str::from_utf8(&rest[..end]).map_err(|_| invalid_data("invalid HDR header"))
+    };
+
+    if !next_line()?.starts_with("#?") {
+        return Err(invalid_data("not a Radiance HDR file"));
+    }
+    // Header variables run until an empty line
+    loop {
+        let line = next_line()?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data(format!("unsupported HDR format {}", format)));
+            }
+        }
+    }
+    let resolution = next_line()?.to_string();
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => match (h.parse::<usize>(), w.parse::<usize>()) {
+            (Ok(h), Ok(w)) if h > 0 && w > 0 => (h, w),
+            _ => return Err(invalid_data("invalid HDR resolution")),
+        },
+        _ => {
+            return Err(invalid_data(format!(
+                "unsupported HDR orientation `{}`",
+                resolution
+            )))
+        }
+    };
+
+    let (Some(count), Some(scanline_size)) = (width.checked_mul(height), width.checked_mul(4))
+    else {
+        return Err(invalid_data("HDR image is too large"));
+    };
+    let truncated = || invalid_data("unexpected end of HDR data");
+    // The shortest a scanline can be is all runs, or flat when too narrow or wide for them, so
+    // a header promising more pixels than the file could hold fails before allocating them
+    let shortest_scanline = if (8..=0x7fff).contains(&width) {
+        4 + 8 * width.div_ceil(127)
+    } else {
+        scanline_size
+    };
+    if height
+        .checked_mul(shortest_scanline)
+        .is_none_or(|size| size > bytes.len() - pos)
+    {
+        return Err(truncated());
+    }
+    let mut pixels = Vec::with_capacity(count);
+    let mut scanline = vec![0u8; scanline_size];
+    for _ in 0..height {
+        let rle = (8..=0x7fff).contains(&width)
+            && bytes.len() >= pos + 4
+            && bytes[pos] == 2
+            && bytes[pos + 1] == 2
+            && bytes[pos + 2] & 0x80 == 0
+            && ((bytes[pos + 2] as usize) << 8 | bytes[pos + 3] as usize) == width;
+        if rle {
+            // Each of the four components in turn, as runs of a repeated byte (count above
+            // 128) or literal stretches of bytes
+            pos += 4;
+            for component in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = *bytes.get(pos).ok_or_else(truncated)? as usize;
+                    pos += 1;
+                    let (run, literal) = if count > 128 {
+                        (count - 128, false)
+                    } else {
+                        (count, true)
+                    };
+                    if run == 0 || x + run > width {
+                        return Err(invalid_data("bad HDR run length"));
+                    }
+                    for i in 0..run {
+                        let value = if literal {
+                            bytes.get(pos + i)
+                        } else {
+                            bytes.get(pos)
+                        };
+                        scanline[(x + i) * 4 + component] = *value.ok_or_else(truncated)?;
+                    }
+                    pos += if literal { run } else { 1 };
+                    x += run;
+                }
+            }
+        } else {
+            let flat = bytes.get(pos..pos + width * 4).ok_or_else(truncated)?;
+            scanline.copy_from_slice(flat);
+            pos += width * 4;
+        }
+        pixels.extend(scanline.chunks(4).map(|rgbe| Pixel {
+            color: from_rgbe(rgbe),
+            alpha: 1.0,
+        }));
+    }
+    Ok(Image::from_pixels(width, height, pixels))
+}
+
+// Round a single precision float to the nearest IEEE 754 half precision value
+fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity stays infinite; NaN keeps a quiet bit set so it stays NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    let round = |value: u32, shift: u32| {
+        let truncated = value >> shift;
+        let remainder = value & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        if remainder > halfway || (remainder == halfway && truncated & 1 == 1) {
+            truncated + 1
+        } else {
+            truncated
+        }
+    };
+    if half_exponent <= 0 {
+        // Too small for a normal half: shift the implicit leading 1 into a subnormal
+        if half_exponent < -10 {
+            return sign;
+        }
+        let shift = (14 - half_exponent) as u32;
+        return sign | round(mantissa | 0x80_0000, shift) as u16;
+    }
+    // A mantissa that rounds up carries into the exponent, which is the right answer
+    sign | round(((half_exponent as u32) << 23) | mantissa, 13) as u16
+}
+
+fn write_exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+// Single-part scanline OpenEXR without compression, one scanline per chunk
+fn write_exr(image: &Image, half: bool, out: &mut dyn Write) -> io::Result<()> {
+    let (width, height) = (image.width(), image.height());
+    // Channels are stored in alphabetical order
+    let channels = ["A", "B", "G", "R"];
+    let pixel_type: i32 = if half { 1 } else { 2 };
+    let sample_size = if half { 2 } else { 4 };
+
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01];
+    header.extend_from_slice(&2i32.to_le_bytes()); // Version 2, single-part scanline
+
+    let mut chlist = Vec::new();
+    for name in channels {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&pixel_type.to_le_bytes());
+        chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved bytes
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    chlist.push(0);
+    write_exr_attribute(&mut header, "channels", "chlist", &chlist);
+    write_exr_attribute(&mut header, "compression", "compression", &[0]);
+
+    let mut window = Vec::new();
+    for v in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    write_exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    write_exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_exr_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    write_exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_exr_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+    out.write_all(&header)?;
+
+    // The offset table points at each scanline chunk from the start of the file
+    let line_size = width * channels.len() * sample_size;
+    let chunk_size = 8 + line_size;
+    let first_chunk = header.len() + 8 * height;
+    let mut offsets = Vec::with_capacity(8 * height);
+    for y in 0..height {
+        offsets.extend_from_slice(&((first_chunk + y * chunk_size) as u64).to_le_bytes());
+    }
+    out.write_all(&offsets)?;
+
+    let mut chunk = Vec::with_capacity(chunk_size);
+    for (y, row) in image.rows().enumerate() {
+        chunk.clear();
+        chunk.extend_from_slice(&(y as i32).to_le_bytes());
+        chunk.extend_from_slice(&(line_size as i32).to_le_bytes());
+        for name in channels {
+            for pixel in row {
+                let value = match name {
+                    "A" => pixel.alpha,
+                    "B" => pixel.color.z(),
+                    "G" => pixel.color.y(),
+                    _ => pixel.color.x(),
+                } as f32;
+                if half {
+                    chunk.extend_from_slice(&f32_to_half(value).to_le_bytes());
+                } else {
+                    chunk.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        out.write_all(&chunk)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_image() -> Image {
+        let pixel = |r, g, b| Pixel {
+            color: Color::new(r, g, b),
+            alpha: 1.0,
+        };
+        Image::from_pixels(2, 1, vec![pixel(1.0, 0.25, 0.0), pixel(4.0, 0.5, 0.125)])
+    }
+
+    #[test]
+    fn converts_to_half() {
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(0.5), 0x3800);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        assert_eq!(f32_to_half(1e6), 0x7c00);
+        assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(0.0), 0x0000);
+    }
+
+    #[test]
+    fn png_round_trips_through_decoder() {
+        let mut bytes = Vec::new();
+        write_image(&test_image(), ImageFormat::Png { bits: 16 }, &mut bytes).unwrap();
+        let decoder = png::Decoder::new(io::Cursor::new(bytes));
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+        // sqrt(0.25) = 0.5 in the green channel of the first pixel
+        assert_eq!(u16::from_be_bytes([buf[2], buf[3]]), 32768);
+    }
+
+    #[test]
+    fn writes_rgbe_and_exr_layout() {
+        assert_eq!(to_rgbe(Color::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+
+        let mut bytes = Vec::new();
+        write_image(&test_image(), ImageFormat::Exr { bits: 16 }, &mut bytes).unwrap();
+        assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+        // One chunk of 2 pixels * 4 channels * 2 bytes, after its 8 byte header
+        let offset = u64::from_le_bytes(bytes[bytes.len() - 32..][..8].try_into().unwrap());
+        assert_eq!(offset as usize, bytes.len() - 24);
+        let first_red = &bytes[bytes.len() - 4..][..2];
+        assert_eq!(u16::from_le_bytes(first_red.try_into().unwrap()), 0x3c00);
+    }
+
+    #[test]
+    fn reads_flat_and_run_length_encoded_hdr() {
+        let mut bytes = Vec::new();
+        write_image(&test_image(), ImageFormat::Hdr, &mut bytes).unwrap();
+        let image = decode_hdr(&bytes).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.pixels()[1].color.x(), 4.0);
+        assert_eq!(image.pixels()[0].color.y(), 0.25);
+
+        // An 8 pixel scanline: red runs 8 times, green is literal, blue and exponent run
+        let mut rle = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+        rle.extend_from_slice(&[2, 2, 0, 8, 128 + 8, 128, 8]);
+        rle.extend_from_slice(&[0, 16, 32, 48, 64, 80, 96, 112]);
+        rle.extend_from_slice(&[128 + 8, 0, 128 + 8, 129]);
+        let image = decode_hdr(&rle).unwrap();
+        assert_eq!(image.pixels()[7].color.x(), 1.0);
+        assert_eq!(image.pixels()[2].color.y(), 0.25);
+        assert!(decode_hdr(&rle[..rle.len() - 1]).is_err());
+
+        // Dimensions far beyond what the data holds are rejected rather than allocated
+        for resolution in ["-Y 4294967296 +X 4294967296", "-Y 100000 +X 100000"] {
+            let huge = format!("#?RADIANCE\n\n{}\n", resolution);
+            let error = decode_hdr(huge.as_bytes()).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn picks_format_from_extension() {
+        let format = |p: &str, bits| ImageFormat::from_path(Path::new(p), bits);
+        assert_eq!(format("a.png", None), Ok(ImageFormat::Png { bits: 8 }));
+        assert_eq!(format("a.EXR", None), Ok(ImageFormat::Exr { bits: 16 }));
+        assert_eq!(format("a.exr", Some(32)), Ok(ImageFormat::Exr { bits: 32 }));
+        assert!(format("a.png", Some(32)).is_err());
+        assert!(format("a.jpg", None).is_err());
+    }
+}
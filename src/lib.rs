@@ -0,0 +1,37 @@
+// A small path tracer: build a `Scene` in code or load one with `scene_file::load_scene`,
+// render it with `Camera::render` into an in-memory `Image`, then save it with `image_io`.
+
+pub mod aabb;
+pub mod background;
+pub mod bsdf;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod demo;
+pub mod disk;
+pub mod distribution;
+pub mod environment;
+pub mod hittable;
+pub mod hittable_list;
+pub mod image;
+pub mod image_io;
+pub mod light;
+pub mod material;
+pub mod medium;
+pub mod microfacet;
+pub mod obj;
+pub mod onb;
+pub mod perlin;
+pub mod quad;
+pub mod ray;
+pub mod rtweekend;
+pub mod sampler;
+pub mod scene;
+pub mod scene_file;
+pub mod sky;
+pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
+pub mod vec3;
+pub mod voxel_grid;
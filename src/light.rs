@@ -0,0 +1,222 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, LightSample},
+    ray::Ray,
+    rtweekend::{degrees_to_radians, PI},
+    sampler::Sampler,
+    vec3::{dot, unit_vector, Color, Point3, Vec3},
+};
+
+// Lights with no surface. Rays never hit them, so they only reach a scene through shadow
+// rays, and they belong in a Scene's lights list rather than its world. Intensities are in
+// W/sr and irradiance in W/m^2, so a light's brightness matches a DiffuseLight surface of
+// the same power.
+
+// Shines equally in every direction from a single point, falling off with the square of the
+// distance
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        PointLight {
+            position,
+            intensity,
+        }
+    }
+
+    // Total power emitted, spread over the full sphere of directions
+    pub fn from_power(position: Point3, power: Color) -> Self {
+        Self::new(position, power / (4.0 * PI))
+    }
+}
+
+impl Hittable for PointLight {
+    fn hit(&self, _: &Ray, _: f64, _: f64, _: &mut HitRecord, _: &mut Sampler) -> bool {
+        false
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.position, self.position)
+    }
+
+    fn is_delta_light(&self) -> bool {
+        true
+    }
+
+    fn illuminate(&self, p: &Point3) -> Option<LightSample> {
+        point_illumination(&self.position, p, self.intensity)
+    }
+}
+
+fn point_illumination(position: &Point3, p: &Point3, intensity: Color) -> Option<LightSample> {
+    let to_light = *position - *p;
+    let distance_squared = to_light.length_squared();
+    if distance_squared <= 0.0 {
+        return None;
+    }
+    let distance = distance_squared.sqrt();
+    Some(LightSample {
+        direction: to_light / distance,
+        distance,
+        irradiance: intensity / distance_squared,
+    })
+}
+
+// A point light shining only into a cone. Full intensity inside inner_angle fades smoothly to
+// nothing at outer_angle, both measured from the axis in degrees.
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3,
+    intensity: Color,
+    cos_inner: f64,
+    cos_outer: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point3,
+        direction: Vec3,
+        intensity: Color,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> Self {
+        let outer_angle = outer_angle.clamp(0.0, 180.0);
+        let inner_angle = inner_angle.clamp(0.0, outer_angle);
+        SpotLight {
+            position,
+            direction: unit_vector(direction),
+            intensity,
+            cos_inner: degrees_to_radians(inner_angle).cos(),
+            cos_outer: degrees_to_radians(outer_angle).cos(),
+        }
+    }
+
+    // Total power emitted, counting the falloff region as if it were lit at half intensity
+    pub fn from_power(
+        position: Point3,
+        direction: Vec3,
+        power: Color,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> Self {
+        let mut light = Self::new(position, direction, power, inner_angle, outer_angle);
+        let solid_angle = 2.0 * PI * (1.0 - 0.5 * (light.cos_inner + light.cos_outer));
+        light.intensity = power / solid_angle.max(1e-12);
+        light
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Hittable for SpotLight {
+    fn hit(&self, _: &Ray, _: f64, _: f64, _: &mut HitRecord, _: &mut Sampler) -> bool {
+        false
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.position, self.position)
+    }
+
+    fn is_delta_light(&self) -> bool {
+        true
+    }
+
+    fn illuminate(&self, p: &Point3) -> Option<LightSample> {
+        let sample = point_illumination(&self.position, p, self.intensity)?;
+        let falloff = self.falloff(dot(-sample.direction, self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            irradiance: sample.irradiance * falloff,
+            ..sample
+        })
+    }
+}
+
+// Parallel light from infinitely far away, like sunlight, arriving along direction with the
+// given irradiance everywhere
+pub struct DirectionalLight {
+    direction: Vec3,
+    irradiance: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        DirectionalLight {
+            direction: unit_vector(direction),
+            irradiance,
+        }
+    }
+}
+
+impl Hittable for DirectionalLight {
+    fn hit(&self, _: &Ray, _: f64, _: f64, _: &mut HitRecord, _: &mut Sampler) -> bool {
+        false
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::empty()
+    }
+
+    fn is_delta_light(&self) -> bool {
+        true
+    }
+
+    fn illuminate(&self, _: &Point3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: f64::INFINITY,
+            irradiance: self.irradiance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falloff_and_power() {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let point =
+            PointLight::from_power(Point3::new(0.0, 2.0, 0.0), Color::new(16.0 * PI, 0.0, 0.0));
+        let sample = point.illuminate(&origin).unwrap();
+        assert_eq!(sample.distance, 2.0);
+        assert_eq!(sample.direction.y(), 1.0);
+        assert!((sample.irradiance.x() - 1.0).abs() < 1e-12);
+
+        let spot = SpotLight::new(
+            Point3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+            10.0,
+            30.0,
+        );
+        let at = |x: f64| {
+            spot.illuminate(&Point3::new(x, 0.0, 0.0))
+                .map(|s| s.irradiance.x())
+        };
+        assert_eq!(at(0.0), Some(1.0));
+        let edge = at(f64::tan(degrees_to_radians(20.0))).unwrap();
+        assert!(edge > 0.0 && edge < 1.0);
+        assert_eq!(at(1.0), None);
+
+        let sun = DirectionalLight::new(Vec3::new(0.0, -2.0, 0.0), Color::new(3.0, 3.0, 3.0));
+        let sample = sun.illuminate(&origin).unwrap();
+        assert_eq!(sample.direction.y(), 1.0);
+        assert!(sample.distance.is_infinite());
+    }
+}
@@ -1,104 +1,265 @@
+use std::sync::Arc;
+
 use crate::{
+    bsdf::{
+        fresnel_dielectric, Bsdf, CoatedBsdf, CompositeBsdf, DiffuseBsdf, DisneyDiffuse, Fresnel,
+        MicrofacetDielectric, MicrofacetReflection, PhaseBsdf,
+    },
+    color::luminance,
     hittable::HitRecord,
+    microfacet::TrowbridgeReitz,
     ray::Ray,
-    rtweekend::random_double,
-    vec3::{
-        dot, random_in_unit_sphere, random_unit_vector, reflect, refract, unit_vector, Color, Vec3,
-    },
+    sampler::Sampler,
+    texture::{SolidColor, Texture},
+    vec3::{dot, reflect, refract, unit_vector, Color, Vec3},
 };
 
+pub enum ScatterRecord {
+    // A single outgoing ray, as from a mirror or smooth glass, whose direction can't be
+    // evaluated or sampled by anything else
+    Specular { attenuation: Color, ray: Ray },
+    // Scattering spread over many directions, which the camera both samples and evaluates
+    // so it can weigh the result against light sampling
+    Bsdf(Box<dyn Bsdf>),
+}
+
 pub trait Material: Send + Sync {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool;
+    // None when the surface absorbs the incoming ray
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord>;
+
+    // Radiance given off by the surface itself, independent of any incoming light
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // Whether this describes scattering inside a medium rather than at a surface. Shadow rays
+    // pass through such hits and account for the medium through Hittable::transmittance.
+    fn is_volumetric(&self) -> bool {
+        false
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    tex: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(a: Color) -> Self {
-        Lambertian { albedo: a }
+        Self::with_texture(Arc::new(SolidColor::new(a)))
+    }
+
+    pub fn with_texture(tex: Arc<dyn Texture>) -> Self {
+        Lambertian { tex }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(
-        &self,
-        _: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = rec.normal + random_unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
-        *scattered = Ray::new(rec.p, scatter_direction);
-        *attenuation = self.albedo;
-        true
+    fn scatter(&self, _: &Ray, rec: &HitRecord, _: &mut Sampler) -> Option<ScatterRecord> {
+        let albedo = self.tex.value(rec.u, rec.v, &rec.p);
+        Some(ScatterRecord::Bsdf(Box::new(DiffuseBsdf::new(
+            albedo, rec.normal,
+        ))))
     }
 }
 
+// A metal whose color at normal incidence comes from a texture, brightening towards white at
+// grazing angles (Schlick). fuzz is the GGX roughness, from 0 for a mirror to 1.
 pub struct Metal {
-    albedo: Color,
+    tex: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
-        Metal { albedo, fuzz }
+        Self::with_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn with_texture(tex: Arc<dyn Texture>, fuzz: f64) -> Self {
+        Metal { tex, fuzz }
     }
 }
 
 impl Material for Metal {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let reflected = reflect(&unit_vector(r_in.direction()), &rec.normal);
-        *scattered = Ray::new(rec.p, reflected + random_in_unit_sphere() * self.fuzz);
-        *attenuation = self.albedo;
-        dot(scattered.direction(), rec.normal) > 0.0
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _: &mut Sampler) -> Option<ScatterRecord> {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(self.fuzz);
+        let fresnel = Fresnel::Schlick(self.tex.value(rec.u, rec.v, &rec.p));
+        Some(microfacet_scatter(r_in, rec, alpha, alpha, fresnel))
+    }
+}
+
+// A metal described by its measured complex index of refraction eta + ik, with GGX roughness
+// that may differ along the two tangent directions of the surface
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    alpha_u: f64,
+    alpha_v: f64,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Self::anisotropic(eta, k, roughness, roughness)
+    }
+
+    // roughness_u runs along the surface's tangent, the direction in which its texture
+    // coordinate u increases, and roughness_v across it
+    pub fn anisotropic(eta: Color, k: Color, roughness_u: f64, roughness_v: f64) -> Self {
+        Conductor {
+            eta,
+            k,
+            alpha_u: TrowbridgeReitz::roughness_to_alpha(roughness_u),
+            alpha_v: TrowbridgeReitz::roughness_to_alpha(roughness_v),
+        }
     }
+
+    // Optical constants at the red, green and blue wavelengths of 650, 550 and 450 nm
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::new(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::new(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    // eta and k, per channel
+    pub fn optical_constants(&self) -> (Color, Color) {
+        (self.eta, self.k)
+    }
+
+    // The preset with the given name, if there is one
+    pub fn preset(name: &str, roughness: f64) -> Option<Self> {
+        match name {
+            "gold" => Some(Self::gold(roughness)),
+            "silver" => Some(Self::silver(roughness)),
+            "copper" => Some(Self::copper(roughness)),
+            "aluminium" => Some(Self::aluminium(roughness)),
+            _ => None,
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _: &mut Sampler) -> Option<ScatterRecord> {
+        let fresnel = Fresnel::Conductor {
+            eta: self.eta,
+            k: self.k,
+        };
+        Some(microfacet_scatter(
+            r_in,
+            rec,
+            self.alpha_u,
+            self.alpha_v,
+            fresnel,
+        ))
+    }
+}
+
+// Reflection off GGX microfacets, or a mirror reflection when the surface is smooth enough
+fn microfacet_scatter(
+    r_in: &Ray,
+    rec: &HitRecord,
+    alpha_x: f64,
+    alpha_y: f64,
+    fresnel: Fresnel,
+) -> ScatterRecord {
+    if TrowbridgeReitz::is_smooth(alpha_x, alpha_y) {
+        let unit_direction = unit_vector(r_in.direction());
+        let cos_theta = dot(-unit_direction, rec.normal);
+        return ScatterRecord::Specular {
+            attenuation: fresnel.reflectance(cos_theta),
+            ray: Ray::with_time(rec.p, reflect(&unit_direction, &rec.normal), r_in.time()),
+        };
+    }
+    ScatterRecord::Bsdf(Box::new(
+        MicrofacetReflection::new(
+            TrowbridgeReitz::new(alpha_x, alpha_y),
+            fresnel,
+            rec.normal,
+            r_in.direction(),
+        )
+        .with_tangent(rec.tangent),
+    ))
 }
 
 pub struct Dielectric {
     refraction_index: f64,
+    exact_fresnel: bool,
+    absorption: Color, // Absorption coefficient per unit length inside, for each channel
 }
 
 impl Dielectric {
     pub fn new(ri: f64) -> Self {
         Dielectric {
             refraction_index: ri,
+            exact_fresnel: false,
+            absorption: Color::new(0.0, 0.0, 0.0),
         }
     }
 
+    // Use the exact Fresnel equations rather than Schlick's approximation of them
+    pub fn with_exact_fresnel(mut self) -> Self {
+        self.exact_fresnel = true;
+        self
+    }
+
+    // Tint light by how far it travels inside (Beer-Lambert), so that it keeps the fraction
+    // color of each channel after covering distance. Each hit on the inside of the surface
+    // is taken to end a path that started on it, so the material suits closed shapes that
+    // don't overlap other dielectrics.
+    pub fn with_absorption(mut self, color: Color, distance: f64) -> Self {
+        let coefficient = |c: f64| -f64::ln(c.clamp(1e-6, 1.0)) / distance;
+        self.absorption = Color::new(
+            coefficient(color.x()),
+            coefficient(color.y()),
+            coefficient(color.z()),
+        );
+        self
+    }
+
+    // Fraction of light reflected at cosine to the normal, where refraction_index is the
+    // index on the incoming side over the one beyond the surface
     pub fn reflectance(&self, cosine: f64, refraction_index: f64) -> f64 {
+        if self.exact_fresnel {
+            return fresnel_dielectric(cosine, 1.0 / refraction_index);
+        }
         // Schlick's approximation for reflectance
         // https://en.wikipedia.org/wiki/Schlick's_approximation
-        let r0: f64 = f64::sqrt((1.0 - refraction_index) / (1.0 + refraction_index));
-        return r0 + (1.0 - r0) * f64::powf(1. - cosine, 5.);
+        let r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
+        let r0 = r0 * r0;
+        r0 + (1.0 - r0) * f64::powf(1. - cosine, 5.)
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
         let ri = if rec.front_face {
             1.0 / self.refraction_index
         } else {
@@ -109,15 +270,638 @@ impl Material for Dielectric {
         let cos_theta = f64::min(dot(-unit_direction, rec.normal), 1.0);
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
         let cannot_refract = ri * sin_theta > 1.0;
-        let direction: Vec3;
-        if cannot_refract || self.reflectance(cos_theta, ri) > random_double() {
-            // Must reflect
-            direction = reflect(&unit_direction, &rec.normal)
+        let direction: Vec3 =
+            if cannot_refract || self.reflectance(cos_theta, ri) > sampler.random_double() {
+                // Must reflect
+                reflect(&unit_direction, &rec.normal)
+            } else {
+                // Must refract
+                refract(&unit_direction, &rec.normal, ri)
+            };
+        // Hitting the inside means r_in crossed the medium to get here
+        let attenuation = if rec.front_face {
+            Color::new(1.0, 1.0, 1.0)
         } else {
-            // Must refract
-            direction = refract(&unit_direction, &rec.normal, ri);
+            let distance = rec.t * r_in.direction().length();
+            Color::new(
+                f64::exp(-self.absorption.x() * distance),
+                f64::exp(-self.absorption.y() * distance),
+                f64::exp(-self.absorption.z() * distance),
+            )
+        };
+        Some(ScatterRecord::Specular {
+            attenuation,
+            ray: Ray::with_time(rec.p, direction, r_in.time()),
+        })
+    }
+}
+
+// Glass with a rough surface, which blurs both what it reflects and what is seen through it.
+// roughness runs from 0, where it behaves exactly like Dielectric with exact Fresnel, to 1.
+pub struct RoughDielectric {
+    smooth: Dielectric,
+    alpha: f64,
+}
+
+impl RoughDielectric {
+    pub fn new(ri: f64, roughness: f64) -> Self {
+        RoughDielectric {
+            smooth: Dielectric::new(ri).with_exact_fresnel(),
+            alpha: TrowbridgeReitz::roughness_to_alpha(roughness),
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        if TrowbridgeReitz::is_smooth(self.alpha, self.alpha) {
+            return self.smooth.scatter(r_in, rec, sampler);
+        }
+        // rec.normal faces the incoming ray, so the far side is inside when it hits the front
+        let ri = self.smooth.refraction_index;
+        let eta = if rec.front_face { ri } else { 1.0 / ri };
+        Some(ScatterRecord::Bsdf(Box::new(MicrofacetDielectric::new(
+            TrowbridgeReitz::new(self.alpha, self.alpha),
+            eta,
+            rec.normal,
+            r_in.direction(),
+        ))))
+    }
+}
+
+// Inputs to the principled material, each a texture so it can vary across the surface. All but
+// base_color are scalars between 0 and 1, read from the first channel of their texture.
+pub struct PrincipledParameters {
+    pub base_color: Arc<dyn Texture>,
+    // Blends from dielectric to metal tinted by base_color
+    pub metallic: Arc<dyn Texture>,
+    // Of the specular, transmission and diffuse lobes
+    pub roughness: Arc<dyn Texture>,
+    // Reflectance of non-metals; 0.5 is 4%, an index of 1.5
+    pub specular: Arc<dyn Texture>,
+    // Extra reflection at grazing angles, as on cloth
+    pub sheen: Arc<dyn Texture>,
+    // A second, glossy and uncolored specular layer
+    pub clearcoat: Arc<dyn Texture>,
+    // Blends from opaque to rough glass
+    pub transmission: Arc<dyn Texture>,
+    // Flattens the diffuse lobe to mimic subsurface scattering
+    pub subsurface: Arc<dyn Texture>,
+}
+
+impl Default for PrincipledParameters {
+    fn default() -> Self {
+        let constant =
+            |v: f64| -> Arc<dyn Texture> { Arc::new(SolidColor::new(Color::new(v, v, v))) };
+        Self {
+            base_color: constant(0.8),
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            sheen: constant(0.0),
+            clearcoat: constant(0.0),
+            transmission: constant(0.0),
+            subsurface: constant(0.0),
         }
-        *scattered = Ray::new(rec.p, direction);
+    }
+}
+
+// An uber-material after the Disney principled BSDF, which covers most opaque and transparent
+// surfaces with a handful of intuitive parameters
+pub struct Principled {
+    parameters: PrincipledParameters,
+}
+
+impl Principled {
+    pub fn new(parameters: PrincipledParameters) -> Self {
+        Principled { parameters }
+    }
+}
+
+// Roughness of the clearcoat layer, which is always glossy
+const CLEARCOAT_ALPHA: f64 = 0.05;
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _: &mut Sampler) -> Option<ScatterRecord> {
+        let params = &self.parameters;
+        let value = |tex: &Arc<dyn Texture>| tex.value(rec.u, rec.v, &rec.p);
+        let scalar = |tex: &Arc<dyn Texture>| value(tex).x().clamp(0.0, 1.0);
+        let base_color = value(&params.base_color);
+        let metallic = scalar(&params.metallic);
+        let roughness = scalar(&params.roughness);
+        let specular = scalar(&params.specular);
+        let transmission = scalar(&params.transmission);
+        let clearcoat = scalar(&params.clearcoat);
+        let gray = |v: f64| Color::new(v, v, v);
+        // Average over the hemisphere of Schlick's approximation starting from f0
+        let average_fresnel = |f0: Color| luminance(f0 + (gray(1.0) - f0) / 21.0);
+
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        let distribution = TrowbridgeReitz::new(alpha, alpha);
+        let incoming = r_in.direction();
+        let mut bsdf = CompositeBsdf::new();
+
+        // Metals and glass have no diffuse lobe
+        let diffuse_weight = (1.0 - metallic) * (1.0 - transmission);
+        bsdf.add(
+            Box::new(DisneyDiffuse::new(
+                base_color,
+                roughness,
+                scalar(&params.subsurface),
+                scalar(&params.sheen),
+                rec.normal,
+                incoming,
+            )),
+            gray(diffuse_weight),
+            diffuse_weight * luminance(base_color),
+        );
+
+        // Specular reflection off everything but the glass, whose own lobe reflects too
+        let f0 = gray(0.08 * specular) * (1.0 - metallic) + base_color * metallic;
+        let specular_weight = 1.0 - (1.0 - metallic) * transmission;
+        bsdf.add(
+            Box::new(MicrofacetReflection::new(
+                distribution,
+                Fresnel::Schlick(f0),
+                rec.normal,
+                incoming,
+            )),
+            gray(specular_weight),
+            specular_weight * average_fresnel(f0),
+        );
+
+        // Glass with the index of refraction that gives the specular reflectance, tinted by
+        // base_color
+        let transmission_weight = (1.0 - metallic) * transmission;
+        if transmission_weight > 0.0 {
+            let r0 = f64::sqrt(0.08 * specular);
+            let ri = ((1.0 + r0) / (1.0 - r0)).max(1.01);
+            let eta = if rec.front_face { ri } else { 1.0 / ri };
+            bsdf.add(
+                Box::new(MicrofacetDielectric::new(
+                    distribution,
+                    eta,
+                    rec.normal,
+                    incoming,
+                )),
+                base_color * transmission_weight,
+                transmission_weight,
+            );
+        }
+
+        let coat = 0.25 * clearcoat;
+        bsdf.add(
+            Box::new(MicrofacetReflection::new(
+                TrowbridgeReitz::new(CLEARCOAT_ALPHA, CLEARCOAT_ALPHA),
+                Fresnel::Schlick(gray(0.04)),
+                rec.normal,
+                incoming,
+            )),
+            gray(coat),
+            coat * average_fresnel(gray(0.04)),
+        );
+
+        Some(ScatterRecord::Bsdf(Box::new(bsdf)))
+    }
+}
+
+// Either of two materials, picked at random at each hit with the probability of the second
+// given by a weight or a mask texture (its first channel). Used for dirt, wear and anything
+// else that blends two surfaces.
+pub struct MixMaterial {
+    first: Arc<dyn Material>,
+    second: Arc<dyn Material>,
+    mask: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(first: Arc<dyn Material>, second: Arc<dyn Material>, weight: f64) -> Self {
+        Self::with_texture(
+            first,
+            second,
+            Arc::new(SolidColor::new(Color::new(weight, weight, weight))),
+        )
+    }
+
+    pub fn with_texture(
+        first: Arc<dyn Material>,
+        second: Arc<dyn Material>,
+        mask: Arc<dyn Texture>,
+    ) -> Self {
+        MixMaterial {
+            first,
+            second,
+            mask,
+        }
+    }
+
+    fn weight(&self, rec: &HitRecord) -> f64 {
+        self.mask.value(rec.u, rec.v, &rec.p).x().clamp(0.0, 1.0)
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        if sampler.random_double() < self.weight(rec) {
+            self.second.scatter(r_in, rec, sampler)
+        } else {
+            self.first.scatter(r_in, rec, sampler)
+        }
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let weight = self.weight(rec);
+        self.first.emitted(r_in, rec) * (1.0 - weight) + self.second.emitted(r_in, rec) * weight
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.first.is_emissive() || self.second.is_emissive()
+    }
+}
+
+// A smooth, clear dielectric layer over any other material, like varnish on wood or the
+// clearcoat on car paint. Light the coat reflects never reaches the base, so the layers
+// together never reflect more than all of the light that arrives.
+pub struct Coated {
+    base: Arc<dyn Material>,
+    refraction_index: f64,
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material>, refraction_index: f64) -> Self {
+        Coated {
+            base,
+            refraction_index,
+        }
+    }
+
+    fn coat_reflectance(&self, direction: &Vec3, rec: &HitRecord) -> f64 {
+        let cosine = dot(unit_vector(*direction), rec.normal).abs();
+        fresnel_dielectric(cosine, self.refraction_index)
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        // Reflect off the coat with the probability it reflects, which leaves a weight of one
+        let unit_direction = unit_vector(r_in.direction());
+        if sampler.random_double() < self.coat_reflectance(&unit_direction, rec) {
+            return Some(ScatterRecord::Specular {
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                ray: Ray::with_time(rec.p, reflect(&unit_direction, &rec.normal), r_in.time()),
+            });
+        }
+        // Otherwise the light got through on the way in, and has yet to on the way out
+        Some(match self.base.scatter(r_in, rec, sampler)? {
+            ScatterRecord::Specular { attenuation, ray } => ScatterRecord::Specular {
+                attenuation: attenuation * (1.0 - self.coat_reflectance(&ray.direction(), rec)),
+                ray,
+            },
+            ScatterRecord::Bsdf(bsdf) => ScatterRecord::Bsdf(Box::new(CoatedBsdf::new(
+                bsdf,
+                self.refraction_index,
+                rec.normal,
+            ))),
+        })
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        self.base.emitted(r_in, rec) * (1.0 - self.coat_reflectance(&r_in.direction(), rec))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+}
+
+pub struct DiffuseLight {
+    tex: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self::with_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn with_texture(tex: Arc<dyn Texture>) -> Self {
+        DiffuseLight { tex }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _: &Ray, _: &HitRecord, _: &mut Sampler) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, _: &Ray, rec: &HitRecord) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p)
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
+
+// Phase function for a medium that scatters light equally in every direction
+pub struct Isotropic {
+    tex: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self::with_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn with_texture(tex: Arc<dyn Texture>) -> Self {
+        Isotropic { tex }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _: &mut Sampler) -> Option<ScatterRecord> {
+        let albedo = self.tex.value(rec.u, rec.v, &rec.p);
+        Some(ScatterRecord::Bsdf(Box::new(PhaseBsdf::new(
+            albedo,
+            0.0,
+            r_in.direction(),
+        ))))
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+// Phase function for a medium that prefers scattering forwards (g > 0), like haze and
+// clouds, or backwards (g < 0)
+pub struct HenyeyGreenstein {
+    tex: Arc<dyn Texture>,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Color, g: f64) -> Self {
+        Self::with_texture(Arc::new(SolidColor::new(albedo)), g)
+    }
+
+    pub fn with_texture(tex: Arc<dyn Texture>, g: f64) -> Self {
+        HenyeyGreenstein { tex, g }
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _: &mut Sampler) -> Option<ScatterRecord> {
+        let albedo = self.tex.value(rec.u, rec.v, &rec.p);
+        Some(ScatterRecord::Bsdf(Box::new(PhaseBsdf::new(
+            albedo,
+            self.g,
+            r_in.direction(),
+        ))))
+    }
+
+    fn is_volumetric(&self) -> bool {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rtweekend::PI,
+        vec3::{random_unit_vector, Point3},
+    };
+
+    #[test]
+    fn dielectric_reflectance_and_absorption() {
+        // Glass reflects 4% head on from either side, and Schlick stays close to the exact
+        // equations away from grazing angles
+        let schlick = Dielectric::new(1.5);
+        let exact = Dielectric::new(1.5).with_exact_fresnel();
+        for ri in [1.0 / 1.5, 1.5] {
+            assert!((schlick.reflectance(1.0, ri) - 0.04).abs() < 1e-12);
+            assert!((exact.reflectance(1.0, ri) - 0.04).abs() < 1e-12);
+        }
+        let cosine = f64::cos(60f64.to_radians());
+        let difference =
+            schlick.reflectance(cosine, 1.0 / 1.5) - exact.reflectance(cosine, 1.0 / 1.5);
+        assert!(difference.abs() < 0.03);
+
+        // A ray leaving through the inside after crossing 2 units keeps color^(2 / 0.5)
+        let glass = Dielectric::new(1.5).with_absorption(Color::new(0.5, 1.0, 0.8), 0.5);
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -0.5));
+        let mut rec = HitRecord {
+            p: Point3::new(0.0, 0.0, -2.0),
+            t: 4.0,
+            ..HitRecord::default()
+        };
+        rec.set_face_normal(&r, &Vec3::new(0.0, 0.0, -1.0));
+        let mut sampler = Sampler::new(24);
+        let Some(ScatterRecord::Specular { attenuation, .. }) =
+            glass.scatter(&r, &rec, &mut sampler)
+        else {
+            panic!("glass scatters specularly");
+        };
+        assert!((attenuation.x() - 0.5f64.powi(4)).abs() < 1e-9);
+        assert!((attenuation.y() - 1.0).abs() < 1e-12);
+        assert!((attenuation.z() - 0.8f64.powi(4)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn anisotropic_conductor_follows_tangent() {
+        // Turning the tangent a quarter turn about the normal turns the highlight with it
+        let brushed = Conductor::anisotropic(
+            Color::new(1.5, 1.5, 1.5),
+            Color::new(3.0, 3.0, 3.0),
+            0.6,
+            0.1,
+        );
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let bsdf_along = |tangent: Vec3| {
+            let mut rec = HitRecord {
+                t: 1.0,
+                tangent,
+                ..HitRecord::default()
+            };
+            rec.set_face_normal(&r, &Vec3::new(0.0, 1.0, 0.0));
+            match brushed.scatter(&r, &rec, &mut Sampler::new(28)) {
+                Some(ScatterRecord::Bsdf(bsdf)) => bsdf,
+                _ => panic!("expected a BSDF"),
+            }
+        };
+        let along_x = bsdf_along(Vec3::new(2.0, 0.0, 0.0));
+        let along_z = bsdf_along(Vec3::new(0.0, 0.0, 1.0));
+        let quarter_turn = |d: Vec3| Vec3::new(-d.z(), d.y(), d.x());
+        let mut sampler = Sampler::new(29);
+        for _ in 0..100 {
+            let direction = random_unit_vector(&mut sampler);
+            let difference = along_x.eval(&direction) - along_z.eval(&quarter_turn(direction));
+            assert!(difference.length() < 1e-9);
+        }
+        // The highlight spreads further along the rougher tangent
+        let tilted = |d: Vec3| along_x.eval(&unit_vector(d)).x();
+        assert!(tilted(Vec3::new(0.5, 1.0, 0.0)) > 2.0 * tilted(Vec3::new(0.0, 1.0, 0.5)));
+    }
+
+    #[test]
+    fn nearly_smooth_rough_dielectric_matches_dielectric() {
+        // Just above the smooth cutoff, rough glass carries as much light through the surface
+        // as the smooth glass it falls back to, entering and leaving
+        let smooth = Dielectric::new(1.5).with_exact_fresnel();
+        let rough = RoughDielectric::new(1.5, f64::sqrt(0.0011));
+        let mut sampler = Sampler::new(27);
+        for outward in [Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0)] {
+            let r = Ray::new(Point3::new(-0.3, 1.0, 0.0), Vec3::new(0.3, -1.0, 0.0));
+            let mut rec = HitRecord {
+                p: Point3::new(0.0, 0.0, 0.0),
+                t: 1.0,
+                ..HitRecord::default()
+            };
+            rec.set_face_normal(&r, &outward);
+            let n = 100_000;
+            let (mut through_smooth, mut through_rough) = (0.0, 0.0);
+            for _ in 0..n {
+                if let Some(ScatterRecord::Specular { attenuation, ray }) =
+                    smooth.scatter(&r, &rec, &mut sampler)
+                {
+                    if ray.direction().y() < 0.0 {
+                        through_smooth += attenuation.x() / n as f64;
+                    }
+                }
+                let Some(ScatterRecord::Bsdf(bsdf)) = rough.scatter(&r, &rec, &mut sampler) else {
+                    panic!("rough glass scatters through a BSDF");
+                };
+                if let Some(direction) = bsdf.sample(&mut sampler) {
+                    if direction.y() < 0.0 {
+                        through_rough +=
+                            bsdf.eval(&direction).x() / bsdf.pdf(&direction) / n as f64;
+                    }
+                }
+            }
+            assert!(
+                (through_smooth - through_rough).abs() < 0.01,
+                "{} vs {}",
+                through_smooth,
+                through_rough
+            );
+        }
+    }
+
+    #[test]
+    fn principled_samples_follow_pdf() {
+        let constant =
+            |v: f64| -> Arc<dyn Texture> { Arc::new(SolidColor::new(Color::new(v, v, v))) };
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -1.0, 0.2));
+        let mut rec = HitRecord {
+            p: Point3::new(0.3, 0.0, 0.2),
+            t: 1.0,
+            ..HitRecord::default()
+        };
+        rec.set_face_normal(&r, &Vec3::new(0.0, 1.0, 0.0));
+        let bsdf_of = |material: &dyn Material, sampler: &mut Sampler| match material
+            .scatter(&r, &rec, sampler)
+        {
+            Some(ScatterRecord::Bsdf(bsdf)) => bsdf,
+            _ => panic!("expected a BSDF"),
+        };
+        let mut sampler = Sampler::new(25);
+
+        // With every lobe switched on, importance sampling and uniform sampling agree
+        let everything = Principled::new(PrincipledParameters {
+            base_color: Arc::new(SolidColor::new(Color::new(0.8, 0.4, 0.2))),
+            metallic: constant(0.3),
+            roughness: constant(0.4),
+            sheen: constant(0.5),
+            clearcoat: constant(1.0),
+            transmission: constant(0.5),
+            subsurface: constant(0.5),
+            ..PrincipledParameters::default()
+        });
+        let bsdf = bsdf_of(&everything, &mut sampler);
+        let n = 400_000;
+        let mut sampled = 0.0;
+        for _ in 0..n {
+            if let Some(direction) = bsdf.sample(&mut sampler) {
+                sampled += bsdf.eval(&direction).y() / bsdf.pdf(&direction) / n as f64;
+            }
+        }
+        let uniform: f64 = (0..n)
+            .map(|_| bsdf.eval(&random_unit_vector(&mut sampler)).y() * 4.0 * PI)
+            .sum::<f64>()
+            / n as f64;
+        assert!(
+            (uniform - sampled).abs() < 0.03 * sampled,
+            "{} vs {}",
+            uniform,
+            sampled
+        );
+
+        // Fully metallic, it reflects exactly like Metal
+        let gold = Color::new(0.9, 0.6, 0.2);
+        let principled = Principled::new(PrincipledParameters {
+            base_color: Arc::new(SolidColor::new(gold)),
+            metallic: constant(1.0),
+            roughness: constant(0.4),
+            ..PrincipledParameters::default()
+        });
+        let principled = bsdf_of(&principled, &mut sampler);
+        let metal = bsdf_of(&Metal::new(gold, 0.4), &mut sampler);
+        for _ in 0..100 {
+            let direction = random_unit_vector(&mut sampler);
+            let difference = principled.eval(&direction) - metal.eval(&direction);
+            assert!(difference.length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn mixed_and_coated_materials() {
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -1.0, 0.2));
+        let mut rec = HitRecord {
+            p: Point3::new(0.3, 0.0, 0.2),
+            t: 1.0,
+            ..HitRecord::default()
+        };
+        rec.set_face_normal(&r, &Vec3::new(0.0, 1.0, 0.0));
+        let mut sampler = Sampler::new(26);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+
+        // The second material is picked in proportion to the weight
+        let red = Arc::new(Lambertian::new(Color::new(1.0, 0.0, 0.0)));
+        let blue = Arc::new(Lambertian::new(Color::new(0.0, 0.0, 1.0)));
+        let mix = MixMaterial::new(red, blue, 0.25);
+        let n = 20_000;
+        let picked_blue = (0..n)
+            .filter(|_| match mix.scatter(&r, &rec, &mut sampler) {
+                Some(ScatterRecord::Bsdf(bsdf)) => bsdf.eval(&up).z() > 0.0,
+                _ => panic!("expected a BSDF"),
+            })
+            .count();
+        assert!((picked_blue as f64 / n as f64 - 0.25).abs() < 0.01);
+
+        // A coated white surface reflects everything, some off the coat and the rest off the
+        // base, less what the coat keeps in on the way out
+        let coated = Coated::new(Arc::new(Lambertian::new(Color::new(1.0, 1.0, 1.0))), 1.5);
+        let n = 200_000;
+        let (mut total, mut off_coat) = (0.0, 0);
+        for _ in 0..n {
+            match coated.scatter(&r, &rec, &mut sampler) {
+                Some(ScatterRecord::Specular { attenuation, .. }) => {
+                    off_coat += 1;
+                    total += attenuation.x() / n as f64;
+                }
+                Some(ScatterRecord::Bsdf(bsdf)) => {
+                    if let Some(direction) = bsdf.sample(&mut sampler) {
+                        total += bsdf.eval(&direction).x() / bsdf.pdf(&direction) / n as f64;
+                    }
+                }
+                None => {}
+            }
+        }
+        let cosine = dot(unit_vector(-r.direction()), up);
+        let reflected = fresnel_dielectric(cosine, 1.5);
+        assert!((off_coat as f64 / n as f64 - reflected).abs() < 0.005);
+        // The cosine-weighted average of the coat's transmission is about 0.9 for glass
+        let expected = reflected + (1.0 - reflected) * 0.9;
+        assert!((total - expected).abs() < 0.02, "{} vs {}", total, expected);
+    }
+}
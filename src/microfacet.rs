@@ -0,0 +1,144 @@
+use crate::{
+    rtweekend::PI,
+    vec3::{cross, dot, unit_vector, Vec3},
+};
+
+// The GGX (Trowbridge-Reitz) distribution of microfacet normals, in a local frame whose z axis
+// is the shading normal. alpha_x and alpha_y set the roughness along x and y; they differ for
+// anisotropic surfaces such as brushed metal.
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self {
+            alpha_x: alpha_x.max(1e-4),
+            alpha_y: alpha_y.max(1e-4),
+        }
+    }
+
+    // Maps an artist-friendly roughness in [0, 1] to alpha, which is roughly linear in how
+    // blurry reflections look
+    pub fn roughness_to_alpha(roughness: f64) -> f64 {
+        roughness.clamp(0.0, 1.0).powi(2)
+    }
+
+    // Below this the surface is treated as perfectly smooth, since the distribution becomes
+    // too peaked to evaluate or sample reliably
+    pub fn is_smooth(alpha_x: f64, alpha_y: f64) -> bool {
+        alpha_x.max(alpha_y) < 1e-3
+    }
+
+    // Density of microfacets with normal h, per unit area of the macrosurface
+    pub fn d(&self, h: &Vec3) -> f64 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let x = h.x() / self.alpha_x;
+        let y = h.y() / self.alpha_y;
+        let t = x * x + y * y + h.z() * h.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * t * t)
+    }
+
+    // Smith's auxiliary function, from which the masking terms follow
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let z2 = w.z() * w.z();
+        if z2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let ax = self.alpha_x * w.x();
+        let ay = self.alpha_y * w.y();
+        0.5 * (f64::sqrt(1.0 + (ax * ax + ay * ay) / z2) - 1.0)
+    }
+
+    // Fraction of microfacets visible from direction w
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Fraction of microfacets visible from both wo and wi, with masking and shadowing
+    // correlated by height
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of the normals sample_visible picks, seen from wo
+    pub fn visible_pdf(&self, wo: &Vec3, h: &Vec3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * f64::max(dot(*wo, *h), 0.0) * self.d(h) / wo.z()
+    }
+
+    // Microfacet normal drawn from those visible from wo, following Heitz, "Sampling the GGX
+    // Distribution of Visible Normals" (2018). wo must lie above the surface.
+    pub fn sample_visible(&self, wo: &Vec3, u1: f64, u2: f64) -> Vec3 {
+        // Stretch wo so the distribution becomes the hemisphere of a unit roughness
+        let vh = unit_vector(Vec3::new(
+            self.alpha_x * wo.x(),
+            self.alpha_y * wo.y(),
+            wo.z(),
+        ));
+        let length_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length_squared > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / length_squared.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = cross(vh, t1);
+
+        // A point on the disk, squashed onto the part of it wo can see
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * f64::sqrt(1.0 - p1 * p1) + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * f64::sqrt(f64::max(0.0, 1.0 - p1 * p1 - p2 * p2));
+
+        // And back to the original roughness
+        unit_vector(Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            f64::max(1e-6, nh.z()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sampler::Sampler, vec3::random_unit_vector};
+
+    #[test]
+    fn distribution_is_normalized() {
+        // The projected area of all microfacets equals the macrosurface, and visible
+        // normals are sampled with the density visible_pdf reports
+        let mut sampler = Sampler::new(21);
+        for (ax, ay) in [(0.3, 0.3), (0.1, 0.6)] {
+            let ggx = TrowbridgeReitz::new(ax, ay);
+            let n = 400_000;
+            let projected: f64 = (0..n)
+                .map(|_| {
+                    let h = random_unit_vector(&mut sampler);
+                    ggx.d(&h) * h.z().max(0.0) * 4.0 * PI
+                })
+                .sum::<f64>()
+                / n as f64;
+            assert!((projected - 1.0).abs() < 0.05, "{}", projected);
+
+            let wo = unit_vector(Vec3::new(0.5, -0.3, 0.8));
+            let visible: f64 = (0..n)
+                .map(|_| ggx.visible_pdf(&wo, &random_unit_vector(&mut sampler)) * 4.0 * PI)
+                .sum::<f64>()
+                / n as f64;
+            assert!((visible - 1.0).abs() < 0.05, "{}", visible);
+            for _ in 0..100 {
+                let h = ggx.sample_visible(&wo, sampler.random_double(), sampler.random_double());
+                assert!(h.z() > 0.0 && dot(h, wo) >= 0.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,587 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    hittable_list::HittableList,
+    material::{Dielectric, Lambertian, Material, Metal, MixMaterial},
+    texture::{ImageTexture, Texture},
+    triangle::{Mesh, TriangleMesh},
+    vec3::{Color, Point3, Vec3},
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        file: String,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+// The triangles of one OBJ group that share a material
+pub struct ObjGroup {
+    pub name: String,
+    pub mesh: Arc<Mesh>,
+    pub material: Arc<dyn Material>,
+}
+
+pub type MaterialLibrary = HashMap<String, Arc<dyn Material>>;
+
+// Load an OBJ file into one TriangleMesh per group and material. Faces without a `usemtl`
+// use default_material; `mtllib` paths are resolved relative to the OBJ file.
+pub fn load_obj(
+    path: &Path,
+    default_material: Arc<dyn Material>,
+) -> Result<HittableList, ObjError> {
+    let source = read_file(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut resolve_mtl = |name: &str| {
+        let mtl_path = dir.join(name);
+        let mtl_source = read_file(&mtl_path)?;
+        parse_mtl(&mtl_path.display().to_string(), &mtl_source)
+    };
+    let groups = parse_obj(
+        &path.display().to_string(),
+        &source,
+        default_material,
+        &mut resolve_mtl,
+    )?;
+
+    let mut list = HittableList::new();
+    for group in groups {
+        list.add(Box::new(TriangleMesh::new(group.mesh, group.material)));
+    }
+    Ok(list)
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn parse_error(file: &str, line: usize, message: impl Into<String>) -> ObjError {
+    ObjError::Parse {
+        file: file.to_string(),
+        line,
+        message: message.into(),
+    }
+}
+
+fn parse_floats<const N: usize>(
+    file: &str,
+    line: usize,
+    args: &[&str],
+    keyword: &str,
+) -> Result<[f64; N], ObjError> {
+    if args.len() < N {
+        return Err(parse_error(
+            file,
+            line,
+            format!("`{}` needs {} values, found {}", keyword, N, args.len()),
+        ));
+    }
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg.parse().map_err(|_| {
+            parse_error(
+                file,
+                line,
+                format!("invalid number `{}` in `{}`", arg, keyword),
+            )
+        })?;
+    }
+    Ok(values)
+}
+
+// A face corner: indices into the position, texture coordinate and normal arrays
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct GroupBuilder {
+    name: String,
+    material: Arc<dyn Material>,
+    faces: Vec<[Corner; 3]>,
+}
+
+pub fn parse_obj(
+    file: &str,
+    source: &str,
+    default_material: Arc<dyn Material>,
+    resolve_mtl: &mut dyn FnMut(&str) -> Result<MaterialLibrary, ObjError>,
+) -> Result<Vec<ObjGroup>, ObjError> {
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut materials = MaterialLibrary::new();
+
+    let mut groups: Vec<GroupBuilder> = Vec::new();
+    let mut group_name = String::from("default");
+    let mut material_name: Option<String> = None;
+    let mut current_material = default_material;
+    let mut current: Option<usize> = None; // Index into groups
+
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let content = raw.split('#').next().unwrap_or("").trim();
+        let mut tokens = content.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats(file, line, &args, keyword)?;
+                positions.push(Point3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(file, line, &args, keyword)?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                // The optional third (w) coordinate is ignored
+                let [u, v] = match args.len() {
+                    1 => {
+                        let [u] = parse_floats(file, line, &args, keyword)?;
+                        [u, 0.0]
+                    }
+                    _ => parse_floats(file, line, &args, keyword)?,
+                };
+                uvs.push((u, v));
+            }
+            "g" | "o" => {
+                group_name = if args.is_empty() {
+                    String::from("default")
+                } else {
+                    args.join(" ")
+                };
+                current = None;
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                current_material = materials.get(&name).cloned().ok_or_else(|| {
+                    parse_error(file, line, format!("unknown material `{}`", name))
+                })?;
+                material_name = Some(name);
+                current = None;
+            }
+            "mtllib" => {
+                for library in &args {
+                    materials.extend(resolve_mtl(library)?);
+                }
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parse_error(
+                        file,
+                        line,
+                        format!("face needs at least 3 vertices, found {}", args.len()),
+                    ));
+                }
+                let corners = args
+                    .iter()
+                    .map(|arg| {
+                        parse_corner(arg, positions.len(), uvs.len(), normals.len())
+                            .map_err(|message| parse_error(file, line, message))
+                    })
+                    .collect::<Result<Vec<Corner>, ObjError>>()?;
+
+                let group = *current.get_or_insert_with(|| {
+                    let name = match &material_name {
+                        Some(m) => format!("{}/{}", group_name, m),
+                        None => group_name.clone(),
+                    };
+                    groups.push(GroupBuilder {
+                        name,
+                        material: current_material.clone(),
+                        faces: Vec::new(),
+                    });
+                    groups.len() - 1
+                });
+                // Split n-gons into a fan around the first corner
+                for i in 1..corners.len() - 1 {
+                    groups[group]
+                        .faces
+                        .push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            // Smoothing groups, lines, points and other statements don't affect triangles
+            _ => {}
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|g| !g.faces.is_empty())
+        .map(|g| build_group(g, &positions, &uvs, &normals))
+        .collect())
+}
+
+// Parse `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolving 1-based and negative relative indices
+fn parse_corner(
+    token: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<Corner, String> {
+    let resolve = |text: &str, count: usize, what: &str| -> Result<usize, String> {
+        let index: i64 = text
+            .parse()
+            .map_err(|_| format!("invalid {} index `{}`", what, text))?;
+        let resolved = if index > 0 {
+            index - 1
+        } else {
+            count as i64 + index
+        };
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(format!("{} index {} out of range", what, index));
+        }
+        Ok(resolved as usize)
+    };
+
+    let mut parts = token.split('/');
+    let position = resolve(parts.next().unwrap_or(""), position_count, "vertex")?;
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(text) => Some(resolve(text, uv_count, "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(text) => Some(resolve(text, normal_count, "normal")?),
+    };
+    Ok((position, uv, normal))
+}
+
+fn build_group(
+    group: GroupBuilder,
+    positions: &[Point3],
+    uvs: &[(f64, f64)],
+    normals: &[Vec3],
+) -> ObjGroup {
+    // OBJ indexes each attribute separately, while Mesh shares one index per vertex,
+    // so every distinct corner becomes its own mesh vertex
+    let corners = group.faces.iter().flatten();
+    let has_uvs = corners.clone().all(|c| c.1.is_some());
+    let has_normals = corners.clone().all(|c| c.2.is_some());
+
+    let mut vertex_of: HashMap<Corner, usize> = HashMap::new();
+    let mut mesh_positions = Vec::new();
+    let mut mesh_uvs = Vec::new();
+    let mut mesh_normals = Vec::new();
+    let mut indices = Vec::with_capacity(group.faces.len());
+    for face in &group.faces {
+        let mut triangle = [0; 3];
+        for (slot, corner) in triangle.iter_mut().zip(face) {
+            let key = (
+                corner.0,
+                corner.1.filter(|_| has_uvs),
+                corner.2.filter(|_| has_normals),
+            );
+            *slot = *vertex_of.entry(key).or_insert_with(|| {
+                mesh_positions.push(positions[corner.0]);
+                if let Some(uv) = key.1 {
+                    mesh_uvs.push(uvs[uv]);
+                }
+                if let Some(n) = key.2 {
+                    mesh_normals.push(normals[n]);
+                }
+                mesh_positions.len() - 1
+            });
+        }
+        indices.push(triangle);
+    }
+
+    let mut mesh = Mesh::new(mesh_positions, indices);
+    if has_uvs {
+        mesh.uvs = Some(mesh_uvs);
+    }
+    if has_normals {
+        mesh.normals = Some(mesh_normals);
+    }
+    ObjGroup {
+        name: group.name,
+        mesh: Arc::new(mesh),
+        material: group.material,
+    }
+}
+
+struct MtlEntry {
+    kd: Color,
+    ks: Color,
+    ns: f64,
+    ni: f64,
+    d: f64,
+    illum: u32,
+    map_kd: Option<Arc<dyn Texture>>,
+}
+
+impl Default for MtlEntry {
+    fn default() -> Self {
+        Self {
+            kd: Color::new(0.8, 0.8, 0.8),
+            ks: Color::new(0.0, 0.0, 0.0),
+            ns: 0.0,
+            ni: 1.5,
+            d: 1.0,
+            illum: 2,
+            map_kd: None,
+        }
+    }
+}
+
+impl MtlEntry {
+    // Map the Phong-style MTL description onto the closest material we can render
+    fn to_material(&self) -> Arc<dyn Material> {
+        // Only these illumination models describe refraction; elsewhere a dissolve below one
+        // just fades the surface out
+        if matches!(self.illum, 4 | 6 | 7 | 9) {
+            return Arc::new(Dielectric::new(self.ni));
+        }
+        let opaque = self.opaque_material();
+        if self.d >= 1.0 {
+            return opaque;
+        }
+        // An index of one lets light straight through without bending or reflecting it
+        let clear = Arc::new(Dielectric::new(1.0));
+        Arc::new(MixMaterial::new(opaque, clear, 1.0 - self.d.max(0.0)))
+    }
+
+    fn opaque_material(&self) -> Arc<dyn Material> {
+        let reflective = matches!(self.illum, 3 | 5 | 8)
+            || (self.kd.length_squared() == 0.0 && self.ks.length_squared() > 0.0);
+        if reflective {
+            // Without Ks, fall back on the diffuse color and then on a perfect mirror
+            let albedo = [self.ks, self.kd]
+                .into_iter()
+                .find(|c| c.length_squared() > 0.0)
+                .unwrap_or(Color::new(1.0, 1.0, 1.0));
+            // Approximate the roughness matching a Blinn-Phong exponent
+            let fuzz = f64::sqrt(2.0 / (self.ns + 2.0)).clamp(0.0, 1.0);
+            Arc::new(Metal::new(albedo, fuzz))
+        } else if let Some(tex) = &self.map_kd {
+            Arc::new(Lambertian::with_texture(tex.clone()))
+        } else {
+            Arc::new(Lambertian::new(self.kd))
+        }
+    }
+}
+
+pub fn parse_mtl(file: &str, source: &str) -> Result<MaterialLibrary, ObjError> {
+    let mut library = MaterialLibrary::new();
+    let mut current: Option<(String, MtlEntry)> = None;
+
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let content = raw.split('#').next().unwrap_or("").trim();
+        let mut tokens = content.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, entry)) = current.take() {
+                library.insert(name, entry.to_material());
+            }
+            if args.is_empty() {
+                return Err(parse_error(file, line, "`newmtl` needs a name"));
+            }
+            current = Some((args.join(" "), MtlEntry::default()));
+            continue;
+        }
+
+        let Some((_, entry)) = current.as_mut() else {
+            return Err(parse_error(
+                file,
+                line,
+                format!("`{}` before any `newmtl`", keyword),
+            ));
+        };
+        match keyword {
+            "Kd" => {
+                let [r, g, b] = parse_floats(file, line, &args, keyword)?;
+                entry.kd = Color::new(r, g, b);
+            }
+            "Ks" => {
+                let [r, g, b] = parse_floats(file, line, &args, keyword)?;
+                entry.ks = Color::new(r, g, b);
+            }
+            "Ns" => [entry.ns] = parse_floats(file, line, &args, keyword)?,
+            "Ni" => [entry.ni] = parse_floats(file, line, &args, keyword)?,
+            "d" => [entry.d] = parse_floats(file, line, &args, keyword)?,
+            "Tr" => {
+                let [tr] = parse_floats(file, line, &args, keyword)?;
+                entry.d = 1.0 - tr;
+            }
+            "illum" => {
+                entry.illum = args.first().and_then(|a| a.parse().ok()).ok_or_else(|| {
+                    parse_error(file, line, "`illum` needs an integer illumination model")
+                })?;
+            }
+            "map_Kd" => {
+                // Options such as -o or -s come before the file name, which is always last
+                let name = args
+                    .last()
+                    .ok_or_else(|| parse_error(file, line, "`map_Kd` needs a file name"))?;
+                let path = Path::new(file).parent().unwrap_or(Path::new("")).join(name);
+                let tex = ImageTexture::load(&path).map_err(|e| {
+                    parse_error(file, line, format!("cannot load {}: {}", path.display(), e))
+                })?;
+                entry.map_kd = Some(Arc::new(tex));
+            }
+            // Ka, Ke, the other texture maps and the rest have no equivalent here yet
+            _ => {}
+        }
+    }
+    if let Some((name, entry)) = current {
+        library.insert(name, entry.to_material());
+    }
+    Ok(library)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::HitRecord, material::ScatterRecord, ray::Ray, sampler::Sampler};
+
+    const MTL: &str = "\
+newmtl red
+Kd 0.8 0.1 0.1
+illum 2
+
+newmtl glass
+Ni 1.45
+d 0.1
+illum 4
+";
+
+    fn parse(source: &str) -> Result<Vec<ObjGroup>, ObjError> {
+        let default: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        parse_obj("test.obj", source, default, &mut |_| {
+            parse_mtl("test.mtl", MTL)
+        })
+    }
+
+    #[test]
+    fn splits_ngons_and_groups() {
+        let groups = parse(
+            "\
+mtllib test.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+g quad
+usemtl red
+f 1/1 2/2 3/3 4/4
+g tri
+usemtl glass
+f -4 -3 -2
+",
+        )
+        .unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].name, "quad/red");
+        assert_eq!(groups[0].mesh.indices.len(), 2);
+        assert_eq!(groups[0].mesh.positions.len(), 4);
+        assert!(groups[0].mesh.uvs.is_some());
+        assert_eq!(groups[1].mesh.indices.len(), 1);
+        assert!(groups[1].mesh.uvs.is_none());
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        let err = parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:3: vertex index 3 out of range");
+
+        let err = parse("mtllib test.mtl\nv 0 x 0\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:2: invalid number `x` in `v`");
+
+        let err = parse("mtllib test.mtl\nusemtl gold\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:2: unknown material `gold`");
+
+        let err = parse_mtl("bad.mtl", "Kd 1 1 1\n").err().unwrap();
+        assert_eq!(err.to_string(), "bad.mtl:1: `Kd` before any `newmtl`");
+    }
+
+    #[test]
+    fn maps_illumination_models() {
+        let library = parse_mtl(
+            "test.mtl",
+            "\
+newmtl mirror
+Kd 0 0 0
+illum 3
+
+newmtl faded
+Kd 0.8 0.1 0.1
+d 0.5
+",
+        )
+        .unwrap();
+        let r = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        rec.set_face_normal(&r, &Vec3::new(0.0, 0.0, 1.0));
+        let mut sampler = Sampler::new(1);
+
+        // illum 3 with neither Ks nor Kd still reflects, as a plain mirror
+        match library["mirror"].scatter(&r, &rec, &mut sampler) {
+            Some(ScatterRecord::Bsdf(bsdf)) => {
+                assert!(!bsdf.eval(&Vec3::new(0.0, 0.0, 1.0)).near_zero())
+            }
+            Some(ScatterRecord::Specular { attenuation, .. }) => assert!(!attenuation.near_zero()),
+            None => panic!("the mirror absorbed the ray"),
+        }
+
+        // Without a refracting model, a dissolve lets part of the light straight through and
+        // scatters the rest off the diffuse surface
+        let n = 1000;
+        let mut passed = 0;
+        for _ in 0..n {
+            match library["faded"].scatter(&r, &rec, &mut sampler) {
+                Some(ScatterRecord::Specular { ray, .. }) => {
+                    assert!((ray.direction() - r.direction()).near_zero());
+                    passed += 1;
+                }
+                Some(ScatterRecord::Bsdf(_)) => {}
+                None => panic!("the faded surface absorbed the ray"),
+            }
+        }
+        assert!((passed as f64 / n as f64 - 0.5).abs() < 0.1);
+    }
+}
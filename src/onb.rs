@@ -0,0 +1,58 @@
+use crate::vec3::{cross, dot, unit_vector, Vec3};
+
+// Orthonormal basis with w along a given direction, for turning directions sampled around
+// the z axis into world space
+#[derive(Clone, Copy)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: Vec3) -> Self {
+        let w = unit_vector(n);
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = unit_vector(cross(w, a));
+        let u = cross(w, v);
+        Self { u, v, w }
+    }
+
+    // Like build_from_w, but with u along tangent projected onto the plane normal to w, or
+    // build_from_w's choice when tangent is zero or parallel to w
+    pub fn build_from_w_and_u(n: Vec3, tangent: Vec3) -> Self {
+        let w = unit_vector(n);
+        let projected = tangent - w * dot(tangent, w);
+        if projected.length_squared() <= 1e-12 * tangent.length_squared() {
+            return Self::build_from_w(w);
+        }
+        let u = unit_vector(projected);
+        let v = cross(u, w);
+        Self { u, v, w }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        self.u * a.x() + self.v * a.y() + self.w * a.z()
+    }
+
+    // Inverse of local: the coordinates of a world space direction along u, v and w
+    pub fn world_to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(dot(a, self.u), dot(a, self.v), dot(a, self.w))
+    }
+}
@@ -0,0 +1,185 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{planar_pdf, HitRecord, Hittable},
+    hittable_list::HittableList,
+    material::Material,
+    ray::Ray,
+    rtweekend::INFINITY,
+    sampler::Sampler,
+    vec3::{cross, dot, unit_vector, Point3, Vec3},
+};
+
+// Parallelogram with corner q and edges u and v. The normal follows u x v, and (u, v) texture
+// coordinates run from 0 to 1 along the two edges.
+#[derive(Clone)]
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3, // n / (n . n), for projecting hit points onto the edges
+    normal: Vec3,
+    d: f64, // Plane equation: normal . p = d
+    area: f64,
+    mat_ptr: Option<Arc<dyn Material>>,
+}
+
+impl Quad {
+    pub fn with_values(q: Point3, u: Vec3, v: Vec3, m: Arc<dyn Material>) -> Self {
+        let n = cross(u, v);
+        let normal = unit_vector(n);
+        Self {
+            q,
+            u,
+            v,
+            w: n / dot(n, n),
+            normal,
+            d: dot(normal, q),
+            area: n.length(),
+            mat_ptr: Some(m),
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord, _: &mut Sampler) -> bool {
+        let denom = dot(self.normal, r.direction());
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+        let t = (self.d - dot(self.normal, r.origin())) / denom;
+        if t < t_min || t_max < t {
+            return false;
+        }
+
+        // Express the hit point in plane coordinates along the two edges
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = dot(self.w, cross(planar_hitpt_vector, self.v));
+        let beta = dot(self.w, cross(self.u, planar_hitpt_vector));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        (rec.u, rec.v) = (alpha, beta);
+        rec.tangent = self.u;
+        rec.set_face_normal(r, &self.normal);
+        rec.mat_ptr = self.mat_ptr.clone();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let diagonal1 = Aabb::new(self.q, self.q + self.u + self.v);
+        let diagonal2 = Aabb::new(self.q + self.u, self.q + self.v);
+        diagonal1.union(&diagonal2).pad(1e-4)
+    }
+
+    // Points are sampled uniformly by area
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let r = Ray::new(*origin, *direction);
+        let mut rec = HitRecord::default();
+        if !self.hit(&r, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)) {
+            return 0.0;
+        }
+        planar_pdf(&r, rec.t, &self.normal, self.area)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let p = self.q + self.u * sampler.random_double() + self.v * sampler.random_double();
+        p - *origin
+    }
+}
+
+// The six faces of the axis-aligned box with opposite corners a and b, all facing outwards
+pub fn make_box(a: Point3, b: Point3, m: Arc<dyn Material>) -> HittableList {
+    let mut sides = HittableList::new();
+
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    let faces = [
+        (Point3::new(min.x(), min.y(), max.z()), dx, dy), // front
+        (Point3::new(max.x(), min.y(), max.z()), -dz, dy), // right
+        (Point3::new(max.x(), min.y(), min.z()), -dx, dy), // back
+        (Point3::new(min.x(), min.y(), min.z()), dz, dy), // left
+        (Point3::new(min.x(), max.y(), max.z()), dx, -dz), // top
+        (Point3::new(min.x(), min.y(), min.z()), dx, dz), // bottom
+    ];
+    for (q, u, v) in faces {
+        sides.add(Box::new(Quad::with_values(q, u, v, m.clone())));
+    }
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, vec3::Color};
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn hit_reports_uv_and_face() {
+        let quad = Quad::with_values(
+            Point3::new(-1., -1., -2.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(0., 4., 0.),
+            material(),
+        );
+        let mut rec = HitRecord::default();
+        let r = Ray::new(Point3::new(0.5, 0., 0.), Vec3::new(0., 0., -1.));
+        assert!(quad.hit(&r, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!((rec.u - 0.75).abs() < 1e-12);
+        assert!((rec.v - 0.25).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal.z() - 1.0).abs() < 1e-12);
+
+        let behind = Ray::new(Point3::new(0.5, 0., -4.), Vec3::new(0., 0., 1.));
+        assert!(quad.hit(&behind, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+        assert!(!rec.front_face);
+        assert!((rec.normal.z() + 1.0).abs() < 1e-12);
+
+        let outside = Ray::new(Point3::new(1.5, 0., 0.), Vec3::new(0., 0., -1.));
+        assert!(!quad.hit(&outside, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+    }
+
+    #[test]
+    fn box_faces_point_outwards() {
+        let sides = make_box(
+            Point3::new(1., 1., 1.),
+            Point3::new(-1., -1., -1.),
+            material(),
+        );
+        let bbox = sides.bounding_box();
+        for a in 0..3 {
+            assert!((bbox.min[a] + 1.0).abs() < 1e-3);
+            assert!((bbox.max[a] - 1.0).abs() < 1e-3);
+        }
+        // Every face is seen from the outside along each axis
+        for direction in [
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0., 1., 0.),
+            Vec3::new(0., 0., 1.),
+        ] {
+            for sign in [1.0, -1.0] {
+                let origin = direction * (3.0 * sign);
+                let r = Ray::new(origin, -origin);
+                let mut rec = HitRecord::default();
+                assert!(sides.hit(&r, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+                assert!(rec.front_face);
+                assert!((rec.t - 2.0 / 3.0).abs() < 1e-12);
+            }
+        }
+    }
+}
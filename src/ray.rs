@@ -4,15 +4,24 @@ use crate::vec3::{Point3, Vec3};
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
+    time: f64, // Moment within the camera's shutter interval the ray exists at
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point3, direction: Vec3, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn at(&self, t: f64) -> Point3 {
-        return self.origin + self.direction * t;
+        self.origin + self.direction * t
     }
 
     pub fn origin(&self) -> Point3 {
@@ -22,4 +31,8 @@ impl Ray {
     pub fn direction(&self) -> Vec3 {
         self.direction
     }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
 }
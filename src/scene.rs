@@ -0,0 +1,63 @@
+use crate::{
+    background::Background,
+    hittable::Hittable,
+    hittable_list::HittableList,
+    sampler::Sampler,
+    vec3::{Point3, Vec3},
+};
+
+// Everything the camera needs to know about the world it renders
+pub struct Scene {
+    pub world: HittableList,
+    // Copies of emissive shapes in world, sampled directly at every diffuse hit. Only shapes
+    // that can be sampled belong here, which rules out moving ones; emitters left out still
+    // light the scene, just with more noise. Delta lights such as point lights live only
+    // here, never in world.
+    pub lights: HittableList,
+    pub background: Background,
+    // Whether camera rays that miss everything see the background. It lights the scene
+    // either way.
+    pub background_visible: bool,
+}
+
+impl Scene {
+    pub fn new(world: HittableList, lights: HittableList, background: Background) -> Self {
+        Self {
+            world,
+            lights,
+            background,
+            background_visible: true,
+        }
+    }
+
+    // Whether there is anything for sample_light to aim shadow rays at. Delta lights are
+    // not sampled but all lit at once, so they don't count here.
+    pub fn has_lights(&self) -> bool {
+        self.lights.sampleable_count() > 0 || self.background.is_sampleable()
+    }
+
+    // Density over solid angle with which sample_light picks direction from origin. When
+    // there are both lights and a sampleable background, each is picked half the time.
+    pub fn light_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let lights = self.lights.sampleable_count() > 0;
+        match (lights, self.background.is_sampleable()) {
+            (true, true) => {
+                0.5 * (self.lights.pdf_value(origin, direction)
+                    + self.background.pdf_value(direction))
+            }
+            (true, false) => self.lights.pdf_value(origin, direction),
+            (false, true) => self.background.pdf_value(direction),
+            (false, false) => 0.0,
+        }
+    }
+
+    pub fn sample_light(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let lights = self.lights.sampleable_count() > 0;
+        let background = self.background.is_sampleable();
+        if background && (!lights || sampler.random_double() < 0.5) {
+            self.background.random(sampler)
+        } else {
+            self.lights.random(origin, sampler)
+        }
+    }
+}
@@ -0,0 +1,1449 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use toml::{Table, Value};
+
+use crate::{
+    aabb::Aabb,
+    background::Background,
+    bvh::BvhNode,
+    camera::CameraSettings,
+    disk::Disk,
+    environment::EnvironmentMap,
+    hittable::Hittable,
+    hittable_list::HittableList,
+    light::{DirectionalLight, PointLight, SpotLight},
+    material::{
+        Coated, Conductor, Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian,
+        Material, Metal, MixMaterial, Principled, PrincipledParameters, RoughDielectric,
+    },
+    medium::{ConstantMedium, GridMedium},
+    obj::load_obj,
+    quad::{make_box, Quad},
+    sampler::Sampler,
+    scene::Scene,
+    sky::Sky,
+    sphere::Sphere,
+    texture::{
+        CheckerTexture, GridTexture, ImageTexture, NoisePattern, NoiseTexture, SolidColor, Texture,
+    },
+    transform::{AnimatedTransform, MovingInstance, Transform, TransformedInstance},
+    triangle::Triangle,
+    vec3::{Color, Vec3},
+    voxel_grid::VoxelGrid,
+};
+
+// Scenes are TOML documents:
+//
+//   [camera]                # lookfrom, lookat, vup, vfov, focus_dist, defocus_angle,
+//                           # shutter_open, shutter_close
+//   [render]                # image_width, aspect_ratio, samples_per_pixel, max_depth, seed
+//   [background]            # type = "none" | "solid" | "gradient" | "environment" | "sky", and
+//                           # visible = false to hide it from the camera
+//   [textures.<name>]       # type = "solid" | "checker" | "image" | "noise" | "grid"
+//   [materials.<name>]      # type = "lambertian" | "metal" | "conductor" | "dielectric"
+//                           #      | "rough_dielectric" | "principled" | "mix" | "coated"
+//                           #      | "diffuse_light" | "isotropic" | "henyey_greenstein"
+//   [[objects]]             # type = "sphere" | "triangle" | "quad" | "disk" | "box" | "obj"
+//                           #      | "medium" | "grid_medium" | "point_light" | "spot_light"
+//                           #      | "directional_light"
+//   [objects.boundary]      # the shape a medium fills, as another object without a material
+//   [objects.transform]     # scale, rotate, axis and angle, translate
+//   [objects.transform_end] # where a moving object ends up at time 1
+//
+// Anywhere a material takes a color, it also accepts the name of a texture, and so do the
+// scalar parameters of principled materials and the weight of mix materials, which read its
+// first channel. Mix and coated materials name the materials they are built from, and checker
+// textures may name textures; either can refer to entries listed later in the file.
+// Relative file paths are resolved against the directory holding the scene file.
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Syntax(toml::de::Error),
+    // A semantic problem with the entry at `path`, e.g. `objects[2].material`
+    Invalid {
+        path: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Syntax(e) => write!(f, "{}", e),
+            SceneError::Invalid { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Syntax(e) => Some(e),
+            SceneError::Invalid { .. } => None,
+        }
+    }
+}
+
+fn invalid(path: &str, message: impl Into<String>) -> SceneError {
+    SceneError::Invalid {
+        path: path.to_string(),
+        message: message.into(),
+    }
+}
+
+pub fn load_scene(path: &Path) -> Result<(Scene, CameraSettings), SceneError> {
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_scene(&source, path.parent().unwrap_or(Path::new("")))
+}
+
+pub fn parse_scene(source: &str, base_dir: &Path) -> Result<(Scene, CameraSettings), SceneError> {
+    let document: Table = source.parse().map_err(SceneError::Syntax)?;
+    let root = Fields {
+        table: &document,
+        path: String::new(),
+    };
+    root.allow_only(&[
+        "camera",
+        "render",
+        "background",
+        "textures",
+        "materials",
+        "objects",
+    ])?;
+
+    let mut settings = CameraSettings::default();
+    if let Some(camera) = root.table_field("camera")? {
+        camera.allow_only(&[
+            "lookfrom",
+            "lookat",
+            "vup",
+            "vfov",
+            "focus_dist",
+            "defocus_angle",
+            "shutter_open",
+            "shutter_close",
+        ])?;
+        settings.lookfrom = camera.vec3_or("lookfrom", settings.lookfrom)?;
+        settings.lookat = camera.vec3_or("lookat", settings.lookat)?;
+        settings.vup = camera.vec3_or("vup", settings.vup)?;
+        settings.vfov = camera.float_or("vfov", settings.vfov)?;
+        if !(settings.vfov > 0.0 && settings.vfov < 180.0) {
+            return Err(invalid(
+                &camera.key_path("vfov"),
+                "expected an angle between 0 and 180 degrees",
+            ));
+        }
+        settings.focus_dist = camera.positive_float_or("focus_dist", settings.focus_dist)?;
+        settings.defocus_angle = camera.float_or("defocus_angle", settings.defocus_angle)?;
+        if !(0.0..).contains(&settings.defocus_angle) {
+            return Err(invalid(
+                &camera.key_path("defocus_angle"),
+                "expected a non-negative angle",
+            ));
+        }
+        settings.shutter_open = camera.float_or("shutter_open", settings.shutter_open)?;
+        settings.shutter_close = camera.float_or("shutter_close", settings.shutter_close)?;
+    }
+    if let Some(render) = root.table_field("render")? {
+        render.allow_only(&[
+            "image_width",
+            "aspect_ratio",
+            "samples_per_pixel",
+            "max_depth",
+            "seed",
+        ])?;
+        settings.image_width = render.count_or("image_width", settings.image_width)?;
+        settings.aspect_ratio = render.positive_float_or("aspect_ratio", settings.aspect_ratio)?;
+        settings.samples_per_pixel =
+            render.count_or("samples_per_pixel", settings.samples_per_pixel)?;
+        settings.max_depth = render.count_or("max_depth", settings.max_depth)?;
+        settings.seed = match render.table.get("seed") {
+            Some(Value::Integer(i)) if *i >= 0 => *i as u64,
+            Some(_) => {
+                return Err(invalid(
+                    &render.key_path("seed"),
+                    "expected a non-negative integer",
+                ))
+            }
+            None => settings.seed,
+        };
+    }
+
+    let (background, background_visible) = match root.table_field("background")? {
+        Some(fields) => (
+            parse_background(&fields, base_dir)?,
+            fields.bool_or("visible", true)?,
+        ),
+        None => (Background::default(), true),
+    };
+
+    let textures = match root.table_field("textures")? {
+        Some(table) => parse_named(
+            &table,
+            "textures",
+            texture_references,
+            |fields, textures| parse_texture(fields, textures, base_dir),
+        )?,
+        None => HashMap::new(),
+    };
+
+    let materials = match root.table_field("materials")? {
+        Some(table) => parse_named(
+            &table,
+            "materials",
+            material_references,
+            |fields, materials| parse_material(fields, &textures, materials),
+        )?,
+        None => HashMap::new(),
+    };
+
+    let mut objects = HittableList::new();
+    let mut lights = HittableList::new();
+    if let Some(value) = document.get("objects") {
+        let entries = value
+            .as_array()
+            .ok_or_else(|| invalid("objects", "expected an array of tables"))?;
+        for (i, entry) in entries.iter().enumerate() {
+            let path = format!("objects[{}]", i);
+            let table = entry
+                .as_table()
+                .ok_or_else(|| invalid(&path, "expected a table"))?;
+            let fields = Fields { table, path };
+            if let Some(light) = parse_light(&fields)? {
+                lights.add(Box::new(light));
+                continue;
+            }
+            let (mut object, mut emissive) =
+                parse_object(&fields, &textures, &materials, base_dir)?;
+            let start = match fields.table_field("transform")? {
+                Some(transform) => Some(parse_transform(&transform)?),
+                None => None,
+            };
+            let end = match fields.table_field("transform_end")? {
+                Some(transform) => Some(parse_transform(&transform)?),
+                None => None,
+            };
+            object = match (start, end) {
+                (None, None) => object,
+                (Some(start), None) => Arc::new(TransformedInstance::new(object, start)),
+                (start, Some(end)) => {
+                    emissive = false;
+                    let motion = AnimatedTransform::new(start.unwrap_or_default(), end);
+                    Arc::new(MovingInstance::new(object, motion))
+                }
+            };
+            if emissive {
+                lights.add(Box::new(object.clone()));
+            }
+            objects.add(Box::new(object));
+        }
+    }
+
+    let world = HittableList::with_object(Box::new(BvhNode::new(objects)));
+    let mut scene = Scene::new(world, lights, background);
+    scene.background_visible = background_visible;
+    Ok((scene, settings))
+}
+
+fn parse_background(fields: &Fields, base_dir: &Path) -> Result<Background, SceneError> {
+    match fields.string("type")? {
+        "none" => {
+            fields.allow_only(&["type", "visible"])?;
+            Ok(Background::None)
+        }
+        "solid" => {
+            fields.allow_only(&["type", "color", "visible"])?;
+            Ok(Background::Solid(fields.vec3("color")?))
+        }
+        "gradient" => {
+            fields.allow_only(&["type", "bottom", "top", "visible"])?;
+            let (bottom, top) = match Background::default() {
+                Background::Gradient { bottom, top } => (bottom, top),
+                _ => unreachable!(),
+            };
+            Ok(Background::Gradient {
+                bottom: fields.vec3_or("bottom", bottom)?,
+                top: fields.vec3_or("top", top)?,
+            })
+        }
+        "environment" => {
+            fields.allow_only(&["type", "path", "rotation", "intensity", "visible"])?;
+            let path = base_dir.join(fields.string("path")?);
+            let map = EnvironmentMap::load(
+                &path,
+                fields.float_or("rotation", 0.0)?,
+                fields.float_or("intensity", 1.0)?,
+            )
+            .map_err(|e| {
+                invalid(
+                    &fields.key_path("path"),
+                    format!("cannot load {}: {}", path.display(), e),
+                )
+            })?;
+            Ok(Background::Environment(map))
+        }
+        "sky" => {
+            fields.allow_only(&[
+                "type",
+                "sun_elevation",
+                "sun_azimuth",
+                "turbidity",
+                "intensity",
+                "visible",
+            ])?;
+            let elevation = fields.float("sun_elevation")?;
+            if !(-90.0..=90.0).contains(&elevation) {
+                return Err(invalid(
+                    &fields.key_path("sun_elevation"),
+                    "expected an angle between -90 and 90 degrees",
+                ));
+            }
+            let turbidity = fields.float_or("turbidity", 3.0)?;
+            if !(1.7..=10.0).contains(&turbidity) {
+                return Err(invalid(
+                    &fields.key_path("turbidity"),
+                    "expected a value between 1.7 and 10",
+                ));
+            }
+            Ok(Background::Sky(Sky::new(
+                elevation,
+                fields.float_or("sun_azimuth", 0.0)?,
+                turbidity,
+                fields.float_or("intensity", 1.0)?,
+            )))
+        }
+        other => {
+            Err(fields.unknown_type(other, &["none", "solid", "gradient", "environment", "sky"]))
+        }
+    }
+}
+
+fn parse_texture(
+    fields: &Fields,
+    textures: &HashMap<String, Arc<dyn Texture>>,
+    base_dir: &Path,
+) -> Result<Arc<dyn Texture>, SceneError> {
+    Ok(match fields.string("type")? {
+        "solid" => {
+            fields.allow_only(&["type", "color"])?;
+            Arc::new(SolidColor::new(fields.vec3("color")?))
+        }
+        "checker" => {
+            fields.allow_only(&["type", "scale", "even", "odd"])?;
+            Arc::new(CheckerTexture::new(
+                fields.float("scale")?,
+                fields.texture("even", textures)?,
+                fields.texture("odd", textures)?,
+            ))
+        }
+        "image" => {
+            fields.allow_only(&["type", "path"])?;
+            let path = base_dir.join(fields.string("path")?);
+            let image = ImageTexture::load(&path).map_err(|e| {
+                invalid(
+                    &fields.key_path("path"),
+                    format!("cannot load {}: {}", path.display(), e),
+                )
+            })?;
+            Arc::new(image)
+        }
+        "noise" => {
+            fields.allow_only(&["type", "scale", "pattern"])?;
+            let pattern = match fields.string_or("pattern", "smooth")? {
+                "smooth" => NoisePattern::Smooth,
+                "turbulence" => NoisePattern::Turbulence,
+                "marble" => NoisePattern::Marble,
+                other => {
+                    return Err(invalid(
+                        &fields.key_path("pattern"),
+                        format!(
+                            "unknown pattern `{}`, expected smooth, turbulence or marble",
+                            other
+                        ),
+                    ))
+                }
+            };
+            // Noise is part of the scene, so it mustn't change with the render seed
+            let mut sampler = Sampler::new(0);
+            Arc::new(NoiseTexture::new(
+                fields.float_or("scale", 1.0)?,
+                pattern,
+                &mut sampler,
+            ))
+        }
+        "grid" => {
+            fields.allow_only(&["type", "path", "resolution", "min", "max"])?;
+            let (grid, bounds) = load_grid(fields, base_dir)?;
+            Arc::new(GridTexture::new(grid, bounds))
+        }
+        other => {
+            return Err(fields.unknown_type(other, &["solid", "checker", "image", "noise", "grid"]))
+        }
+    })
+}
+
+// Parses every entry of a table of named textures or materials. Entries built from others in
+// the same table wait until those exist, whatever order they are listed in.
+fn parse_named<'a, T>(
+    table: &Fields<'a>,
+    kind: &str,
+    references: fn(&Fields<'a>) -> Vec<&'a str>,
+    mut parse: impl FnMut(&Fields<'a>, &HashMap<String, T>) -> Result<T, SceneError>,
+) -> Result<HashMap<String, T>, SceneError> {
+    let mut parsed: HashMap<String, T> = HashMap::new();
+    let mut pending: Vec<&String> = table.table.keys().collect();
+    while !pending.is_empty() {
+        let ready = pending
+            .iter()
+            .position(|name| match table.table_field(name) {
+                Ok(Some(fields)) => references(&fields)
+                    .iter()
+                    .all(|r| parsed.contains_key(*r) || !table.table.contains_key(*r)),
+                _ => true,
+            });
+        let Some(index) = ready else {
+            return Err(invalid(
+                &table.key_path(pending[0]),
+                format!("{} refer to each other in a cycle", kind),
+            ));
+        };
+        let name = pending.remove(index);
+        let fields = table.table_field(name)?.unwrap();
+        let value = parse(&fields, &parsed)?;
+        parsed.insert(name.clone(), value);
+    }
+    Ok(parsed)
+}
+
+// Names of the other textures a texture is built from
+fn texture_references<'a>(fields: &Fields<'a>) -> Vec<&'a str> {
+    let keys: &[&str] = match fields.table.get("type").and_then(Value::as_str) {
+        Some("checker") => &["even", "odd"],
+        _ => &[],
+    };
+    keys.iter()
+        .filter_map(|key| fields.table.get(*key).and_then(Value::as_str))
+        .collect()
+}
+
+// Names of the other materials a material is built from
+fn material_references<'a>(fields: &Fields<'a>) -> Vec<&'a str> {
+    let keys: &[&str] = match fields.table.get("type").and_then(Value::as_str) {
+        Some("mix") => &["first", "second"],
+        Some("coated") => &["base"],
+        _ => &[],
+    };
+    keys.iter()
+        .filter_map(|key| fields.table.get(*key).and_then(Value::as_str))
+        .collect()
+}
+
+fn parse_material(
+    fields: &Fields,
+    textures: &HashMap<String, Arc<dyn Texture>>,
+    materials: &HashMap<String, Arc<dyn Material>>,
+) -> Result<Arc<dyn Material>, SceneError> {
+    Ok(match fields.string("type")? {
+        "lambertian" => {
+            fields.allow_only(&["type", "albedo"])?;
+            Arc::new(Lambertian::with_texture(
+                fields.texture("albedo", textures)?,
+            ))
+        }
+        "metal" => {
+            fields.allow_only(&["type", "albedo", "fuzz"])?;
+            Arc::new(Metal::with_texture(
+                fields.texture("albedo", textures)?,
+                fields.float_or("fuzz", 0.0)?,
+            ))
+        }
+        "conductor" => {
+            fields.allow_only(&[
+                "type",
+                "preset",
+                "eta",
+                "k",
+                "roughness",
+                "roughness_u",
+                "roughness_v",
+            ])?;
+            let roughness = |key: &str, default: f64| {
+                let r = fields.float_or(key, default)?;
+                if !(0.0..=1.0).contains(&r) {
+                    return Err(invalid(
+                        &fields.key_path(key),
+                        "expected a value between 0 and 1",
+                    ));
+                }
+                Ok(r)
+            };
+            let isotropic = roughness("roughness", 0.0)?;
+            let roughness_u = roughness("roughness_u", isotropic)?;
+            let roughness_v = roughness("roughness_v", isotropic)?;
+            // The optical constants come either from a named preset or given directly
+            let (eta, k) = if fields.table.contains_key("preset") {
+                if fields.table.contains_key("eta") || fields.table.contains_key("k") {
+                    return Err(invalid(
+                        &fields.key_path("preset"),
+                        "a preset can't be combined with eta and k",
+                    ));
+                }
+                let name = fields.string("preset")?;
+                let Some(metal) = Conductor::preset(name, 0.0) else {
+                    return Err(invalid(
+                        &fields.key_path("preset"),
+                        format!(
+                            "unknown preset `{}`, expected one of: gold, silver, copper, aluminium",
+                            name
+                        ),
+                    ));
+                };
+                metal.optical_constants()
+            } else {
+                (fields.vec3("eta")?, fields.vec3("k")?)
+            };
+            Arc::new(Conductor::anisotropic(eta, k, roughness_u, roughness_v))
+        }
+        "dielectric" => {
+            fields.allow_only(&[
+                "type",
+                "refraction_index",
+                "fresnel",
+                "absorption",
+                "absorption_distance",
+            ])?;
+            let mut dielectric = Dielectric::new(fields.float("refraction_index")?);
+            match fields.string_or("fresnel", "schlick")? {
+                "schlick" => {}
+                "exact" => dielectric = dielectric.with_exact_fresnel(),
+                other => {
+                    return Err(invalid(
+                        &fields.key_path("fresnel"),
+                        format!(
+                            "unknown fresnel `{}`, expected one of: schlick, exact",
+                            other
+                        ),
+                    ))
+                }
+            }
+            if fields.table.contains_key("absorption") {
+                let distance = fields.float_or("absorption_distance", 1.0)?;
+                if distance <= 0.0 {
+                    return Err(invalid(
+                        &fields.key_path("absorption_distance"),
+                        "expected a positive number",
+                    ));
+                }
+                dielectric = dielectric.with_absorption(fields.vec3("absorption")?, distance);
+            }
+            Arc::new(dielectric)
+        }
+        "rough_dielectric" => {
+            fields.allow_only(&["type", "refraction_index", "roughness"])?;
+            let roughness = fields.float("roughness")?;
+            if !(0.0..=1.0).contains(&roughness) {
+                return Err(invalid(
+                    &fields.key_path("roughness"),
+                    "expected a value between 0 and 1",
+                ));
+            }
+            Arc::new(RoughDielectric::new(
+                fields.float("refraction_index")?,
+                roughness,
+            ))
+        }
+        "principled" => {
+            fields.allow_only(&[
+                "type",
+                "base_color",
+                "metallic",
+                "roughness",
+                "specular",
+                "sheen",
+                "clearcoat",
+                "transmission",
+                "subsurface",
+            ])?;
+            let defaults = PrincipledParameters::default();
+            let base_color = if fields.table.contains_key("base_color") {
+                fields.texture("base_color", textures)?
+            } else {
+                defaults.base_color
+            };
+            Arc::new(Principled::new(PrincipledParameters {
+                base_color,
+                metallic: fields.scalar_texture_or("metallic", defaults.metallic, textures)?,
+                roughness: fields.scalar_texture_or("roughness", defaults.roughness, textures)?,
+                specular: fields.scalar_texture_or("specular", defaults.specular, textures)?,
+                sheen: fields.scalar_texture_or("sheen", defaults.sheen, textures)?,
+                clearcoat: fields.scalar_texture_or("clearcoat", defaults.clearcoat, textures)?,
+                transmission: fields.scalar_texture_or(
+                    "transmission",
+                    defaults.transmission,
+                    textures,
+                )?,
+                subsurface: fields.scalar_texture_or(
+                    "subsurface",
+                    defaults.subsurface,
+                    textures,
+                )?,
+            }))
+        }
+        "mix" => {
+            fields.allow_only(&["type", "first", "second", "weight"])?;
+            let first = fields.material("first", materials)?;
+            let second = fields.material("second", materials)?;
+            let default = Arc::new(SolidColor::new(Color::new(0.5, 0.5, 0.5)));
+            Arc::new(MixMaterial::with_texture(
+                first,
+                second,
+                fields.scalar_texture_or("weight", default, textures)?,
+            ))
+        }
+        "coated" => {
+            fields.allow_only(&["type", "base", "refraction_index"])?;
+            Arc::new(Coated::new(
+                fields.material("base", materials)?,
+                fields.float_or("refraction_index", 1.5)?,
+            ))
+        }
+        "diffuse_light" => {
+            fields.allow_only(&["type", "emit"])?;
+            Arc::new(DiffuseLight::with_texture(
+                fields.texture("emit", textures)?,
+            ))
+        }
+        "isotropic" => {
+            fields.allow_only(&["type", "albedo"])?;
+            Arc::new(Isotropic::with_texture(fields.texture("albedo", textures)?))
+        }
+        "henyey_greenstein" => {
+            fields.allow_only(&["type", "albedo", "g"])?;
+            let g = fields.float("g")?;
+            if g <= -1.0 || g >= 1.0 {
+                return Err(invalid(
+                    &fields.key_path("g"),
+                    "expected a value between -1 and 1",
+                ));
+            }
+            Arc::new(HenyeyGreenstein::with_texture(
+                fields.texture("albedo", textures)?,
+                g,
+            ))
+        }
+        other => {
+            return Err(fields.unknown_type(
+                other,
+                &[
+                    "lambertian",
+                    "metal",
+                    "conductor",
+                    "dielectric",
+                    "rough_dielectric",
+                    "principled",
+                    "mix",
+                    "coated",
+                    "diffuse_light",
+                    "isotropic",
+                    "henyey_greenstein",
+                ],
+            ))
+        }
+    })
+}
+
+// The object built from one [[objects]] entry, and whether it gives off light
+fn parse_object(
+    fields: &Fields,
+    textures: &HashMap<String, Arc<dyn Texture>>,
+    materials: &HashMap<String, Arc<dyn Material>>,
+    base_dir: &Path,
+) -> Result<(Arc<dyn Hittable>, bool), SceneError> {
+    Ok(match fields.string("type")? {
+        "sphere" => {
+            fields.allow_only(&[
+                "type",
+                "center",
+                "end_center",
+                "radius",
+                "material",
+                "transform",
+                "transform_end",
+            ])?;
+            let material = fields.material("material", materials)?;
+            let emissive = material.is_emissive();
+            let center = fields.vec3("center")?;
+            let sphere = Sphere::moving(
+                center,
+                fields.vec3_or("end_center", center)?,
+                fields.float("radius")?,
+                material,
+            );
+            // A moving light can't be sampled, so it is only found by bouncing into it
+            let emissive = emissive && !sphere.is_moving();
+            (Arc::new(sphere), emissive)
+        }
+        "triangle" => {
+            fields.allow_only(&["type", "vertices", "material", "transform", "transform_end"])?;
+            let path = fields.key_path("vertices");
+            let vertices = fields
+                .get("vertices")?
+                .as_array()
+                .filter(|v| v.len() == 3)
+                .ok_or_else(|| invalid(&path, "expected an array of three points"))?;
+            let p = |i: usize| as_vec3(&vertices[i], &format!("{}[{}]", path, i));
+            let material = fields.material("material", materials)?;
+            let emissive = material.is_emissive();
+            let triangle = Triangle::with_values(p(0)?, p(1)?, p(2)?, material);
+            (Arc::new(triangle), emissive)
+        }
+        "quad" => {
+            fields.allow_only(&[
+                "type",
+                "q",
+                "u",
+                "v",
+                "material",
+                "transform",
+                "transform_end",
+            ])?;
+            let material = fields.material("material", materials)?;
+            let emissive = material.is_emissive();
+            let quad = Quad::with_values(
+                fields.vec3("q")?,
+                fields.vec3("u")?,
+                fields.vec3("v")?,
+                material,
+            );
+            (Arc::new(quad), emissive)
+        }
+        "disk" => {
+            fields.allow_only(&[
+                "type",
+                "center",
+                "normal",
+                "radius",
+                "material",
+                "transform",
+                "transform_end",
+            ])?;
+            let material = fields.material("material", materials)?;
+            let emissive = material.is_emissive();
+            let disk = Disk::with_values(
+                fields.vec3("center")?,
+                fields.vec3("normal")?,
+                fields.float("radius")?,
+                material,
+            );
+            (Arc::new(disk), emissive)
+        }
+        "box" => {
+            fields.allow_only(&[
+                "type",
+                "min",
+                "max",
+                "material",
+                "transform",
+                "transform_end",
+            ])?;
+            let material = fields.material("material", materials)?;
+            let emissive = material.is_emissive();
+            let sides = make_box(fields.vec3("min")?, fields.vec3("max")?, material);
+            (Arc::new(sides), emissive)
+        }
+        "obj" => {
+            fields.allow_only(&["type", "path", "material", "transform", "transform_end"])?;
+            let path = base_dir.join(fields.string("path")?);
+            let default_material = match fields.table.get("material") {
+                Some(_) => fields.material("material", materials)?,
+                None => Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            };
+            let model = load_obj(&path, default_material)
+                .map_err(|e| invalid(&fields.key_path("path"), e.to_string()))?;
+            // Meshes can't be sampled as lights
+            (Arc::new(BvhNode::new(model)), false)
+        }
+        "medium" => {
+            fields.allow_only(&[
+                "type",
+                "density",
+                "material",
+                "boundary",
+                "transform",
+                "transform_end",
+            ])?;
+            let density = fields.float("density")?;
+            if density <= 0.0 {
+                return Err(invalid(
+                    &fields.key_path("density"),
+                    "expected a positive number",
+                ));
+            }
+            let material = fields.phase_function("material", materials)?;
+            let boundary = fields
+                .table_field("boundary")?
+                .ok_or_else(|| invalid(&fields.key_path("boundary"), "missing field"))?;
+            if let Some(key) = ["transform", "transform_end"]
+                .into_iter()
+                .find(|k| boundary.table.contains_key(*k))
+            {
+                return Err(invalid(
+                    &boundary.key_path(key),
+                    "transform the medium rather than its boundary",
+                ));
+            }
+            // The boundary is never shaded, so it borrows the medium's material rather than
+            // needing one of its own
+            let mut table = boundary.table.clone();
+            table
+                .entry("material")
+                .or_insert_with(|| fields.table["material"].clone());
+            let boundary = Fields {
+                table: &table,
+                path: boundary.path,
+            };
+            let (shape, _) = parse_object(&boundary, textures, materials, base_dir)?;
+            let medium = ConstantMedium::new(shape, density, material);
+            (Arc::new(medium), false)
+        }
+        "grid_medium" => {
+            fields.allow_only(&[
+                "type",
+                "path",
+                "resolution",
+                "min",
+                "max",
+                "density",
+                "material",
+                "emission",
+                "transform",
+                "transform_end",
+            ])?;
+            let (grid, bounds) = load_grid(fields, base_dir)?;
+            let density = fields.float_or("density", 1.0)?;
+            if density < 0.0 {
+                return Err(invalid(
+                    &fields.key_path("density"),
+                    "expected a non-negative number",
+                ));
+            }
+            let mut medium = GridMedium::new(
+                bounds,
+                grid,
+                density,
+                fields.phase_function("material", materials)?,
+            );
+            if fields.table.contains_key("emission") {
+                medium = medium.with_emission(fields.texture("emission", textures)?);
+            }
+            (Arc::new(medium), false)
+        }
+        other => {
+            return Err(fields.unknown_type(
+                other,
+                &[
+                    "sphere",
+                    "triangle",
+                    "quad",
+                    "disk",
+                    "box",
+                    "obj",
+                    "medium",
+                    "grid_medium",
+                    "point_light",
+                    "spot_light",
+                    "directional_light",
+                ],
+            ))
+        }
+    })
+}
+
+// Lights with no surface, which go straight into the scene's lights rather than its world.
+// Point and spot lights take either an intensity in W/sr or their total power in W.
+fn parse_light(fields: &Fields) -> Result<Option<Arc<dyn Hittable>>, SceneError> {
+    let intensity_or_power = |fields: &Fields| match (
+        fields.table.contains_key("intensity"),
+        fields.table.contains_key("power"),
+    ) {
+        (true, false) => Ok((fields.vec3("intensity")?, false)),
+        (false, true) => Ok((fields.vec3("power")?, true)),
+        _ => Err(invalid(
+            &fields.path,
+            "expected exactly one of intensity and power",
+        )),
+    };
+    Ok(Some(match fields.string("type")? {
+        "point_light" => {
+            fields.allow_only(&["type", "position", "intensity", "power"])?;
+            let position = fields.vec3("position")?;
+            match intensity_or_power(fields)? {
+                (power, true) => Arc::new(PointLight::from_power(position, power)),
+                (intensity, false) => Arc::new(PointLight::new(position, intensity)),
+            }
+        }
+        "spot_light" => {
+            fields.allow_only(&[
+                "type",
+                "position",
+                "direction",
+                "intensity",
+                "power",
+                "inner_angle",
+                "outer_angle",
+            ])?;
+            let position = fields.vec3("position")?;
+            let direction = fields.vec3("direction")?;
+            let outer_angle = fields.float("outer_angle")?;
+            if !(0.0..=180.0).contains(&outer_angle) {
+                return Err(invalid(
+                    &fields.key_path("outer_angle"),
+                    "expected an angle between 0 and 180 degrees",
+                ));
+            }
+            let inner_angle = fields.float_or("inner_angle", outer_angle)?;
+            if !(0.0..=outer_angle).contains(&inner_angle) {
+                return Err(invalid(
+                    &fields.key_path("inner_angle"),
+                    "expected an angle between 0 and outer_angle",
+                ));
+            }
+            match intensity_or_power(fields)? {
+                (power, true) => Arc::new(SpotLight::from_power(
+                    position,
+                    direction,
+                    power,
+                    inner_angle,
+                    outer_angle,
+                )),
+                (intensity, false) => Arc::new(SpotLight::new(
+                    position,
+                    direction,
+                    intensity,
+                    inner_angle,
+                    outer_angle,
+                )),
+            }
+        }
+        "directional_light" => {
+            fields.allow_only(&["type", "direction", "irradiance"])?;
+            Arc::new(DirectionalLight::new(
+                fields.vec3("direction")?,
+                fields.vec3("irradiance")?,
+            ))
+        }
+        _ => return Ok(None),
+    }))
+}
+
+// A voxel grid from a .vol file, or a raw one given its resolution, and the box it fills.
+// min and max override the bounds a .vol file comes with, and raw grids need them.
+fn load_grid(fields: &Fields, base_dir: &Path) -> Result<(Arc<VoxelGrid>, Aabb), SceneError> {
+    let resolution = match fields.table.get("resolution") {
+        Some(value) => {
+            let path = fields.key_path("resolution");
+            let error = || invalid(&path, "expected an array of three positive integers");
+            let counts = value
+                .as_array()
+                .filter(|a| a.len() == 3)
+                .ok_or_else(error)?;
+            let mut resolution = [0; 3];
+            for (n, count) in resolution.iter_mut().zip(counts) {
+                *n = count
+                    .as_integer()
+                    .filter(|&i| i > 0)
+                    .and_then(|i| usize::try_from(i).ok())
+                    .ok_or_else(error)?;
+            }
+            Some(resolution)
+        }
+        None => None,
+    };
+    let path = base_dir.join(fields.string("path")?);
+    let (grid, file_bounds) = VoxelGrid::load(&path, resolution).map_err(|e| {
+        invalid(
+            &fields.key_path("path"),
+            format!("cannot load {}: {}", path.display(), e),
+        )
+    })?;
+    let bounds = match file_bounds {
+        Some(b) => Aabb::new(fields.vec3_or("min", b.min)?, fields.vec3_or("max", b.max)?),
+        None => Aabb::new(fields.vec3("min")?, fields.vec3("max")?),
+    };
+    Ok((Arc::new(grid), bounds))
+}
+
+// Always applied in the same order, whatever order the keys are written in: scale, rotate
+// (Euler angles in degrees about x, then y, then z), a rotation by angle degrees about axis,
+// then translate
+fn parse_transform(fields: &Fields) -> Result<Transform, SceneError> {
+    fields.allow_only(&["scale", "rotate", "axis", "angle", "translate"])?;
+    let mut transform = Transform::identity();
+    if let Some(value) = fields.table.get("scale") {
+        let path = fields.key_path("scale");
+        let factors = match as_f64(value) {
+            Some(s) => Vec3::new(s, s, s),
+            None => as_vec3(value, &path)?,
+        };
+        if (0..3).any(|a| factors[a] == 0.0) {
+            return Err(invalid(&path, "scale factors must not be zero"));
+        }
+        transform = transform.then(&Transform::scale(factors));
+    }
+    if fields.table.contains_key("rotate") {
+        transform = transform.then(&Transform::rotate_euler(fields.vec3("rotate")?));
+    }
+    if fields.table.contains_key("axis") || fields.table.contains_key("angle") {
+        let axis = fields.vec3("axis")?;
+        if axis.near_zero() {
+            return Err(invalid(&fields.key_path("axis"), "axis must not be zero"));
+        }
+        transform = transform.then(&Transform::rotate(axis, fields.float("angle")?));
+    }
+    if fields.table.contains_key("translate") {
+        transform = transform.then(&Transform::translate(fields.vec3("translate")?));
+    }
+    Ok(transform)
+}
+
+fn as_vec3(value: &Value, path: &str) -> Result<Vec3, SceneError> {
+    let components = value
+        .as_array()
+        .filter(|a| a.len() == 3)
+        .ok_or_else(|| invalid(path, "expected an array of three numbers"))?;
+    let mut v = Vec3::default();
+    for (i, component) in components.iter().enumerate() {
+        v[i] =
+            as_f64(component).ok_or_else(|| invalid(path, "expected an array of three numbers"))?;
+    }
+    Ok(v)
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(f) => Some(*f),
+        Value::Integer(i) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+// A TOML table together with its location in the document, for error messages
+struct Fields<'a> {
+    table: &'a Table,
+    path: String,
+}
+
+impl<'a> Fields<'a> {
+    fn key_path(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    fn allow_only(&self, keys: &[&str]) -> Result<(), SceneError> {
+        match self.table.keys().find(|k| !keys.contains(&k.as_str())) {
+            Some(key) => Err(invalid(&self.key_path(key), "unknown field")),
+            None => Ok(()),
+        }
+    }
+
+    fn unknown_type(&self, found: &str, expected: &[&str]) -> SceneError {
+        invalid(
+            &self.key_path("type"),
+            format!(
+                "unknown type `{}`, expected one of: {}",
+                found,
+                expected.join(", ")
+            ),
+        )
+    }
+
+    fn get(&self, key: &str) -> Result<&'a Value, SceneError> {
+        self.table
+            .get(key)
+            .ok_or_else(|| invalid(&self.key_path(key), "missing field"))
+    }
+
+    fn table_field(&self, key: &str) -> Result<Option<Fields<'a>>, SceneError> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(Value::Table(table)) => Ok(Some(Fields {
+                table,
+                path: self.key_path(key),
+            })),
+            Some(_) => Err(invalid(&self.key_path(key), "expected a table")),
+        }
+    }
+
+    fn float(&self, key: &str) -> Result<f64, SceneError> {
+        as_f64(self.get(key)?).ok_or_else(|| invalid(&self.key_path(key), "expected a number"))
+    }
+
+    fn float_or(&self, key: &str, default: f64) -> Result<f64, SceneError> {
+        match self.table.get(key) {
+            Some(_) => self.float(key),
+            None => Ok(default),
+        }
+    }
+
+    fn positive_float_or(&self, key: &str, default: f64) -> Result<f64, SceneError> {
+        let v = self.float_or(key, default)?;
+        if !(v > 0.0 && v.is_finite()) {
+            return Err(invalid(&self.key_path(key), "expected a positive number"));
+        }
+        Ok(v)
+    }
+
+    fn count_or(&self, key: &str, default: u32) -> Result<u32, SceneError> {
+        match self.table.get(key) {
+            Some(Value::Integer(i)) if *i > 0 && *i <= u32::MAX as i64 => Ok(*i as u32),
+            Some(_) => Err(invalid(&self.key_path(key), "expected a positive integer")),
+            None => Ok(default),
+        }
+    }
+
+    fn bool_or(&self, key: &str, default: bool) -> Result<bool, SceneError> {
+        match self.table.get(key) {
+            Some(Value::Boolean(b)) => Ok(*b),
+            Some(_) => Err(invalid(&self.key_path(key), "expected true or false")),
+            None => Ok(default),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<&'a str, SceneError> {
+        self.get(key)?
+            .as_str()
+            .ok_or_else(|| invalid(&self.key_path(key), "expected a string"))
+    }
+
+    fn string_or(&self, key: &str, default: &'a str) -> Result<&'a str, SceneError> {
+        match self.table.get(key) {
+            Some(_) => self.string(key),
+            None => Ok(default),
+        }
+    }
+
+    fn vec3(&self, key: &str) -> Result<Vec3, SceneError> {
+        as_vec3(self.get(key)?, &self.key_path(key))
+    }
+
+    fn vec3_or(&self, key: &str, default: Vec3) -> Result<Vec3, SceneError> {
+        match self.table.get(key) {
+            Some(_) => self.vec3(key),
+            None => Ok(default),
+        }
+    }
+
+    // Either a color given inline or the name of a texture
+    fn texture(
+        &self,
+        key: &str,
+        textures: &HashMap<String, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        match self.get(key)? {
+            Value::String(name) => textures
+                .get(name)
+                .cloned()
+                .ok_or_else(|| invalid(&self.key_path(key), format!("unknown texture `{}`", name))),
+            _ => Ok(Arc::new(SolidColor::new(self.vec3(key)?))),
+        }
+    }
+
+    // A number between 0 and 1 or the name of a texture, falling back to default when absent
+    fn scalar_texture_or(
+        &self,
+        key: &str,
+        default: Arc<dyn Texture>,
+        textures: &HashMap<String, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        match self.table.get(key) {
+            None => Ok(default),
+            Some(Value::String(_)) => self.texture(key, textures),
+            Some(_) => {
+                let v = self.float(key)?;
+                if !(0.0..=1.0).contains(&v) {
+                    return Err(invalid(
+                        &self.key_path(key),
+                        "expected a value between 0 and 1",
+                    ));
+                }
+                Ok(Arc::new(SolidColor::new(Color::new(v, v, v))))
+            }
+        }
+    }
+
+    fn material(
+        &self,
+        key: &str,
+        materials: &HashMap<String, Arc<dyn Material>>,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        let name = self.string(key)?;
+        materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| invalid(&self.key_path(key), format!("unknown material `{}`", name)))
+    }
+
+    // A material that scatters inside a medium rather than off a surface
+    fn phase_function(
+        &self,
+        key: &str,
+        materials: &HashMap<String, Arc<dyn Material>>,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        let material = self.material(key, materials)?;
+        if !material.is_volumetric() {
+            return Err(invalid(
+                &self.key_path(key),
+                "expected an isotropic or henyey_greenstein material",
+            ));
+        }
+        Ok(material)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = r#"
+[camera]
+lookfrom = [13, 2, 3]
+lookat = [0, 0, 0]
+vfov = 20
+
+[render]
+image_width = 320
+samples_per_pixel = 4
+
+[background]
+type = "solid"
+color = [0.1, 0.1, 0.1]
+
+[textures.checker]
+type = "checker"
+scale = 0.5
+even = [0.2, 0.3, 0.1]
+odd = [0.9, 0.9, 0.9]
+
+[materials.ground]
+type = "lambertian"
+albedo = "checker"
+
+[materials.light]
+type = "diffuse_light"
+emit = [4, 4, 4]
+
+[[objects]]
+type = "sphere"
+center = [0, -1000, 0]
+radius = 1000
+material = "ground"
+
+[[objects]]
+type = "triangle"
+vertices = [[0, 0, 0], [1, 0, 0], [0, 1, 0]]
+material = "light"
+"#;
+
+    fn error_of(source: &str) -> String {
+        match parse_scene(source, Path::new("")) {
+            Ok(_) => panic!("scene should not parse"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_scene() {
+        let (scene, settings) = parse_scene(SCENE, Path::new("")).unwrap();
+        assert_eq!(settings.image_width, 320);
+        assert_eq!(settings.samples_per_pixel, 4);
+        assert_eq!(settings.vfov, 20.0);
+        assert_eq!(settings.max_depth, CameraSettings::default().max_depth);
+        assert!(matches!(scene.background, Background::Solid(_)));
+        assert_eq!(scene.world.objects.len(), 1);
+    }
+
+    #[test]
+    fn parses_example_scenes() {
+        let scenes = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        let (_, settings) = load_scene(&scenes.join("three_spheres.toml")).unwrap();
+        assert_eq!(settings.image_width, 400);
+        let (scene, _) = load_scene(&scenes.join("cornell_box.toml")).unwrap();
+        assert_eq!(scene.lights.objects.len(), 1);
+        let (scene, settings) = load_scene(&scenes.join("motion_blur.toml")).unwrap();
+        assert_eq!(settings.shutter_close, 1.0);
+        assert!(scene.lights.objects.is_empty());
+        let (scene, _) = load_scene(&scenes.join("cornell_smoke.toml")).unwrap();
+        assert_eq!(scene.lights.objects.len(), 1);
+        let (scene, _) = load_scene(&scenes.join("cloud.toml")).unwrap();
+        assert_eq!(scene.world.bounding_box().max.y(), 8.0 + 1.0);
+        let (scene, _) = load_scene(&scenes.join("environment.toml")).unwrap();
+        assert!(scene.background.is_sampleable());
+        assert!(scene.has_lights());
+        let (scene, _) = load_scene(&scenes.join("sky.toml")).unwrap();
+        assert!(matches!(scene.background, Background::Sky(_)));
+        let (scene, _) = load_scene(&scenes.join("spotlights.toml")).unwrap();
+        assert_eq!(scene.lights.objects.len(), 4);
+        assert!(!scene.has_lights());
+        let (scene, _) = load_scene(&scenes.join("metals.toml")).unwrap();
+        assert_eq!(scene.world.objects.len(), 1);
+        let (scene, _) = load_scene(&scenes.join("frosted_glass.toml")).unwrap();
+        assert!(scene.has_lights());
+        let (scene, _) = load_scene(&scenes.join("principled.toml")).unwrap();
+        assert!(scene.background.is_sampleable());
+        let (scene, _) = load_scene(&scenes.join("layered.toml")).unwrap();
+        assert!(scene.background.is_sampleable());
+    }
+
+    #[test]
+    fn parses_raw_grid_medium() {
+        let dir = std::env::temp_dir().join(format!("grid-medium-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let bytes: Vec<u8> = (0..8).flat_map(|i| (i as f32).to_le_bytes()).collect();
+        fs::write(dir.join("density.raw"), bytes).unwrap();
+        let source = r#"
+[materials.fire]
+type = "isotropic"
+albedo = [0.5, 0.5, 0.5]
+
+[[objects]]
+type = "grid_medium"
+path = "density.raw"
+resolution = [2, 2, 2]
+min = [-1, -1, -1]
+max = [1, 1, 1]
+material = "fire"
+emission = [4, 2, 1]
+"#;
+        let (scene, _) = parse_scene(source, &dir).unwrap();
+        assert_eq!(scene.world.bounding_box().min.x(), -1.0);
+
+        let missing_bounds = source.replace("min = [-1, -1, -1]\n", "");
+        let error = parse_scene(&missing_bounds, &dir).err().unwrap();
+        assert_eq!(error.to_string(), "objects[0].min: missing field");
+
+        // A surface material would make the medium an opaque wall
+        let opaque = source.replace("type = \"isotropic\"", "type = \"lambertian\"");
+        let error = parse_scene(&opaque, &dir).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "objects[0].material: expected an isotropic or henyey_greenstein material"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn textures_may_refer_to_later_ones() {
+        // Checkers can be built from textures listed after them, but not in a circle
+        let nested = SCENE.replace(
+            "odd = [0.9, 0.9, 0.9]",
+            "odd = \"marble\"\n\n[textures.marble]\ntype = \"noise\"\npattern = \"marble\"",
+        );
+        assert!(parse_scene(&nested, Path::new("")).is_ok());
+        let circular = SCENE.replace("odd = [0.9, 0.9, 0.9]", "odd = \"checker\"");
+        assert_eq!(
+            error_of(&circular),
+            "textures.checker: textures refer to each other in a cycle"
+        );
+    }
+
+    #[test]
+    fn errors_name_the_offending_entry() {
+        let unknown = SCENE.replace("material = \"light\"", "material = \"lamp\"");
+        assert_eq!(
+            error_of(&unknown),
+            "objects[1].material: unknown material `lamp`"
+        );
+
+        let missing = SCENE.replace("radius = 1000\n", "");
+        assert_eq!(error_of(&missing), "objects[0].radius: missing field");
+
+        let hidden = SCENE.replace("type = \"solid\"", "type = \"solid\"\nvisible = 0");
+        assert_eq!(
+            error_of(&hidden),
+            "background.visible: expected true or false"
+        );
+
+        let bad = SCENE.replace("scale = 0.5", "scale = \"big\"");
+        assert_eq!(error_of(&bad), "textures.checker.scale: expected a number");
+
+        let typo = SCENE.replace("vfov = 20", "fov = 20");
+        assert_eq!(error_of(&typo), "camera.fov: unknown field");
+
+        let light = format!(
+            "{}\n[[objects]]\ntype = \"point_light\"\nposition = [0, 1, 0]\n",
+            SCENE
+        );
+        assert_eq!(
+            error_of(&light),
+            "objects[2]: expected exactly one of intensity and power"
+        );
+
+        let metal = SCENE.replace(
+            "type = \"diffuse_light\"\nemit = [4, 4, 4]",
+            "type = \"conductor\"\npreset = \"tin\"",
+        );
+        assert_eq!(
+            error_of(&metal),
+            "materials.light.preset: unknown preset `tin`, expected one of: gold, silver, copper, \
+             aluminium"
+        );
+
+        // Materials may refer to ones defined after them, but not in a circle
+        let layered = SCENE.replace(
+            "type = \"lambertian\"\nalbedo = \"checker\"",
+            "type = \"mix\"\nfirst = \"light\"\nsecond = \"varnished\"\n\n\
+             [materials.varnished]\ntype = \"coated\"\nbase = \"light\"",
+        );
+        assert!(parse_scene(&layered, Path::new("")).is_ok());
+        let circular = layered.replace("base = \"light\"", "base = \"ground\"");
+        assert_eq!(
+            error_of(&circular),
+            "materials.ground: materials refer to each other in a cycle"
+        );
+
+        let glass = SCENE.replace(
+            "type = \"diffuse_light\"\nemit = [4, 4, 4]",
+            "type = \"dielectric\"\nrefraction_index = 1.5\nfresnel = \"fast\"",
+        );
+        assert_eq!(
+            error_of(&glass),
+            "materials.light.fresnel: unknown fresnel `fast`, expected one of: schlick, exact"
+        );
+
+        let flat = SCENE.replace(
+            "samples_per_pixel = 4",
+            "samples_per_pixel = 4\naspect_ratio = 0",
+        );
+        assert_eq!(
+            error_of(&flat),
+            "render.aspect_ratio: expected a positive number"
+        );
+        let wide = SCENE.replace("vfov = 20", "vfov = 180");
+        assert_eq!(
+            error_of(&wide),
+            "camera.vfov: expected an angle between 0 and 180 degrees"
+        );
+        let blurry = SCENE.replace("vfov = 20", "vfov = 20\ndefocus_angle = -1");
+        assert_eq!(
+            error_of(&blurry),
+            "camera.defocus_angle: expected a non-negative angle"
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{
+    onb::Onb,
+    rtweekend::{degrees_to_radians, PI},
+    sampler::Sampler,
+    vec3::{dot, unit_vector, Color, Vec3},
+};
+
+// Angular radius of the sun as seen from the earth, in degrees
+const SUN_ANGULAR_RADIUS: f64 = 0.2665;
+// Luminance of the sun above the atmosphere in kcd/m^2, the unit the sky model works in.
+// SKY_SCALE brings both to values around one for a clear midday sky.
+const SUN_LUMINANCE: f64 = 1.6e6;
+const SKY_SCALE: f64 = 0.05;
+
+// Daylight from the analytic sky of Preetham, Shirley and Smits, "A Practical Analytic Model
+// for Daylight" (1999), plus the sun as a small disk that direct lighting can aim at.
+// Turbidity runs from about 2 for a very clear sky to 10 for haze.
+pub struct Sky {
+    sun_direction: Vec3,
+    sun_color: Color, // Radiance of the sun disk after passing through the atmosphere
+    cos_sun_radius: f64, // Cosine of the sun's angular radius
+    zenith: [f64; 3], // Y, x and y straight up
+    perez: [[f64; 5]; 3], // Perez coefficients A to E for Y, x and y
+    zenith_normalizer: [f64; 3], // Perez function at the zenith, for each of Y, x and y
+    intensity: f64,
+}
+
+impl Sky {
+    // Elevation is in degrees above the horizon, and azimuth in degrees from -z towards +x
+    pub fn new(sun_elevation: f64, sun_azimuth: f64, turbidity: f64, intensity: f64) -> Self {
+        let elevation = degrees_to_radians(sun_elevation);
+        let azimuth = degrees_to_radians(sun_azimuth);
+        let sun_direction = Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+
+        // The model only covers a sun at or above the horizon
+        let theta_s = (PI / 2.0 - elevation).clamp(0.0, PI / 2.0);
+        let t = turbidity;
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let cubic =
+            |c: [f64; 4]| c[0] * theta_s.powi(3) + c[1] * theta_s.powi(2) + c[2] * theta_s + c[3];
+        let zenith_x = t * t * cubic([0.00166, -0.00375, 0.00209, 0.0])
+            + t * cubic([-0.02903, 0.06377, -0.03202, 0.00394])
+            + cubic([0.11693, -0.21196, 0.06052, 0.25886]);
+        let zenith_y = t * t * cubic([0.00275, -0.00610, 0.00317, 0.0])
+            + t * cubic([-0.04214, 0.08970, -0.04153, 0.00516])
+            + cubic([0.15346, -0.26756, 0.06670, 0.26688]);
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+        let zenith_normalizer = perez.map(|c| perez_function(&c, 1.0, theta_s));
+
+        Self {
+            sun_direction,
+            sun_color: sun_transmittance(theta_s, turbidity) * SUN_LUMINANCE * SKY_SCALE,
+            cos_sun_radius: degrees_to_radians(SUN_ANGULAR_RADIUS).cos(),
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+            perez,
+            zenith_normalizer,
+            intensity,
+        }
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    pub fn value(&self, direction: &Vec3) -> Color {
+        let d = unit_vector(*direction);
+        let sun = if dot(d, self.sun_direction) >= self.cos_sun_radius && d.y() > 0.0 {
+            self.sun_color
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+        (self.sky(&d) + sun) * self.intensity
+    }
+
+    // Only the sun is sampled; the rest of the sky is smooth enough for BSDF sampling
+    pub fn pdf_value(&self, direction: &Vec3) -> f64 {
+        if dot(unit_vector(*direction), self.sun_direction) < self.cos_sun_radius {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - self.cos_sun_radius))
+    }
+
+    // Direction uniformly distributed over the sun disk
+    pub fn random(&self, sampler: &mut Sampler) -> Vec3 {
+        let r1 = sampler.random_double();
+        let r2 = sampler.random_double();
+        let z = 1.0 + r2 * (self.cos_sun_radius - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = f64::sqrt(1.0 - z * z);
+        Onb::build_from_w(self.sun_direction).local(Vec3::new(
+            f64::cos(phi) * sin_theta,
+            f64::sin(phi) * sin_theta,
+            z,
+        ))
+    }
+
+    fn sky(&self, d: &Vec3) -> Color {
+        // Below the horizon, carry on with the color at the horizon
+        let cos_theta = d.y().max(0.001);
+        let gamma = dot(*d, self.sun_direction).clamp(-1.0, 1.0).acos();
+        let [luminance, x, y] = std::array::from_fn(|i| {
+            self.zenith[i] * perez_function(&self.perez[i], cos_theta, gamma)
+                / self.zenith_normalizer[i]
+        });
+        let luminance = luminance.max(0.0) * SKY_SCALE;
+        // xyY to XYZ to linear sRGB
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        Color::new(
+            (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+            (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+            (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+        )
+    }
+}
+
+// Relative brightness of a sky element at zenith angle theta and angle gamma from the sun
+fn perez_function(c: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    (1.0 + c[0] * f64::exp(c[1] / cos_theta))
+        * (1.0 + c[2] * f64::exp(c[3] * gamma) + c[4] * gamma.cos() * gamma.cos())
+}
+
+// Fraction of sunlight reaching the ground in the red, green and blue parts of the spectrum,
+// from Rayleigh scattering and aerosol (Angstrom) extinction along the sun's path through the
+// atmosphere, following the appendix of Preetham et al.
+fn sun_transmittance(theta_s: f64, turbidity: f64) -> Color {
+    let air_mass = 1.0 / (theta_s.cos() + 0.15 * f64::powf(93.885 - theta_s.to_degrees(), -1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let channel = |wavelength: f64| {
+        // Wavelength in micrometers
+        let rayleigh = 0.008735 * wavelength.powf(-4.08);
+        let aerosol = beta * wavelength.powf(-1.3);
+        f64::exp(-air_mass * (rayleigh + aerosol))
+    };
+    Color::new(channel(0.65), channel(0.57), channel(0.475))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sky_is_brighter_near_the_sun_and_bluer_overhead() {
+        let sky = Sky::new(30.0, 90.0, 3.0, 1.0);
+        assert!((sky.sun_direction() - Vec3::new(0.866, 0.5, 0.0)).length() < 1e-3);
+
+        let overhead = sky.value(&Vec3::new(0.0, 1.0, 0.0));
+        let near_sun = sky.value(&Vec3::new(0.8, 0.6, 0.0));
+        let away = sky.value(&Vec3::new(-0.8, 0.6, 0.0));
+        assert!(near_sun.y() > away.y());
+        assert!(overhead.z() > overhead.x());
+        assert!(overhead.y() > 0.1 && overhead.y() < 10.0, "{}", overhead);
+
+        // The sun disk is far brighter than the sky and reddens towards the horizon
+        let sun = sky.value(&sky.sun_direction());
+        assert!(sun.y() > 1000.0 * overhead.y());
+        let low_sun = Sky::new(3.0, 0.0, 3.0, 1.0);
+        let low = low_sun.value(&low_sun.sun_direction());
+        assert!(low.x() / low.z() > sun.x() / sun.z());
+    }
+
+    #[test]
+    fn sun_samples_match_pdf() {
+        let sky = Sky::new(45.0, 10.0, 2.5, 1.0);
+        let mut sampler = Sampler::new(12);
+        for _ in 0..1000 {
+            let direction = sky.random(&mut sampler);
+            assert!(sky.pdf_value(&direction) > 0.0);
+            assert!(sky.value(&direction).x() > 100.0);
+        }
+        assert_eq!(sky.pdf_value(&Vec3::new(0.0, 1.0, 0.0)), 0.0);
+    }
+}
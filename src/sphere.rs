@@ -1,30 +1,73 @@
 use std::sync::Arc;
 
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
     material::Material,
+    onb::Onb,
     ray::Ray,
-    vec3::{dot, Point3},
+    rtweekend::{INFINITY, PI},
+    sampler::Sampler,
+    vec3::{dot, Point3, Vec3},
 };
+
+#[derive(Clone)]
 pub struct Sphere {
-    center: Point3,
+    center: Point3, // Center at time 0
+    motion: Vec3,   // Distance the center travels between time 0 and time 1
     radius: f64,
     mat_ptr: Option<Arc<dyn Material>>,
 }
 
 impl Sphere {
     pub fn with_values(center: Point3, radius: f64, m: Arc<dyn Material>) -> Self {
+        Self::moving(center, center, radius, m)
+    }
+
+    // A sphere moving in a straight line from center0 at time 0 to center1 at time 1. It rests
+    // at either end outside of that interval.
+    pub fn moving(center0: Point3, center1: Point3, radius: f64, m: Arc<dyn Material>) -> Self {
         Self {
-            center,
+            center: center0,
+            motion: center1 - center0,
             radius,
             mat_ptr: Some(m),
         }
     }
+
+    pub fn is_moving(&self) -> bool {
+        self.motion.length_squared() > 0.0
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        self.center + self.motion * time.clamp(0.0, 1.0)
+    }
+}
+
+// Map a point on the unit sphere to texture coordinates: u is the angle around the y axis
+// starting from -x, v the angle from -y to +y, both scaled to [0, 1]
+fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+    let theta = f64::acos(-p.y());
+    let phi = f64::atan2(-p.z(), p.x()) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+// Direction, around +z, uniformly distributed over the cone that a sphere of the given radius
+// subtends from distance_squared away
+fn random_to_sphere(sampler: &mut Sampler, radius: f64, distance_squared: f64) -> Vec3 {
+    let r1 = sampler.random_double();
+    let r2 = sampler.random_double();
+    let cos_theta_max = f64::sqrt(1.0 - radius * radius / distance_squared);
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
+    let phi = 2.0 * PI * r1;
+    let sin_theta = f64::sqrt(1.0 - z * z);
+    Vec3::new(f64::cos(phi) * sin_theta, f64::sin(phi) * sin_theta, z)
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let oc = r.origin() - self.center;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord, _: &mut Sampler) -> bool {
+        let center = self.center(r.time());
+        let oc = r.origin() - center;
         let a = r.direction().length_squared();
         let half_b = dot(oc, r.direction());
         let c = oc.length_squared() - self.radius * self.radius;
@@ -45,9 +88,121 @@ impl Hittable for Sphere {
 
         rec.t = root;
         rec.p = r.at(rec.t);
-        let outward_normal = (rec.p - self.center) / self.radius;
+        let outward_normal = (rec.p - center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = get_sphere_uv(&outward_normal);
+        // Around the y axis, the way u runs
+        rec.tangent = Vec3::new(outward_normal.z(), 0.0, -outward_normal.x());
         rec.mat_ptr = self.mat_ptr.clone();
         true
     }
+
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        let start = Aabb::new(self.center - rvec, self.center + rvec);
+        let end = Aabb::new(self.center(1.0) - rvec, self.center(1.0) + rvec);
+        start.union(&end)
+    }
+
+    // Only the cone of directions that can see the sphere is sampled, uniformly by solid angle.
+    // Where that cone lies depends on the time, so a moving sphere can't be sampled.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.is_moving() {
+            return 0.0;
+        }
+        let distance_squared = (self.center - *origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 0.0;
+        }
+        let mut rec = HitRecord::default();
+        if !self.hit(
+            &Ray::new(*origin, *direction),
+            0.001,
+            INFINITY,
+            &mut rec,
+            &mut Sampler::new(0),
+        ) {
+            return 0.0;
+        }
+        let cos_theta_max = f64::sqrt(1.0 - self.radius * self.radius / distance_squared);
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let direction = self.center - *origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            // From inside, any direction reaches the surface
+            return direction;
+        }
+        let uvw = Onb::build_from_w(direction);
+        uvw.local(random_to_sphere(sampler, self.radius, distance_squared))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, vec3::Color};
+
+    #[test]
+    fn sampled_directions_hit_and_match_pdf() {
+        let sphere = Sphere::with_values(
+            Point3::new(0.0, 0.0, -4.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let origin = Point3::new(0.5, 0.2, 0.0);
+        let mut sampler = Sampler::new(3);
+        let expected = sphere.pdf_value(&origin, &(Point3::new(0.0, 0.0, -4.0) - origin));
+        assert!(expected > 0.0);
+        for _ in 0..1000 {
+            let direction = sphere.random(&origin, &mut sampler);
+            // Uniform over the cone, so every sampled direction has the same density
+            assert!((sphere.pdf_value(&origin, &direction) - expected).abs() < 1e-9);
+        }
+        assert_eq!(sphere.pdf_value(&origin, &Vec3::new(0.0, 0.0, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn moving_sphere_follows_time() {
+        let sphere = Sphere::moving(
+            Point3::new(0.0, 0.0, -4.0),
+            Point3::new(2.0, 0.0, -4.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let bbox = sphere.bounding_box();
+        assert_eq!((bbox.min.x(), bbox.max.x()), (-0.5, 2.5));
+
+        let r = |x, time| Ray::with_time(Point3::new(x, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), time);
+        let mut rec = HitRecord::default();
+        assert!(sphere.hit(
+            &r(0.0, 0.0),
+            0.001,
+            INFINITY,
+            &mut rec,
+            &mut Sampler::new(0)
+        ));
+        assert!(!sphere.hit(
+            &r(0.0, 1.0),
+            0.001,
+            INFINITY,
+            &mut rec,
+            &mut Sampler::new(0)
+        ));
+        assert!(sphere.hit(
+            &r(1.0, 0.5),
+            0.001,
+            INFINITY,
+            &mut rec,
+            &mut Sampler::new(0)
+        ));
+        assert!((rec.t - 3.5).abs() < 1e-9);
+        assert_eq!(
+            sphere.pdf_value(&Point3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 0.0, -1.0)),
+            0.0
+        );
+    }
 }
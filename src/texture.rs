@@ -0,0 +1,343 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    aabb::Aabb,
+    image_io::load_hdr,
+    perlin::Perlin,
+    sampler::Sampler,
+    vec3::{Color, Point3},
+    voxel_grid::VoxelGrid,
+};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        SolidColor { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _: f64, _: f64, _: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+// Alternates between two textures on a 3D grid of cubes with sides of length `scale`
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        CheckerTexture {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        Self::new(
+            scale,
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x = (self.inv_scale * p.x()).floor() as i64;
+        let y = (self.inv_scale * p.y()).floor() as i64;
+        let z = (self.inv_scale * p.z()).floor() as i64;
+        if (x + y + z).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// Texture looked up from a PPM, PNG or Radiance HDR file, repeating outside of [0, 1] UV space
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>, // Linear color, row-major from the top-left
+}
+
+impl ImageTexture {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let (width, height, pixels) = match extension.as_deref() {
+            Some("png") => decode_png(path)?,
+            // HDR files already hold linear values
+            Some("hdr") => {
+                let image = load_hdr(path)?;
+                let pixels = image.pixels().iter().map(|p| p.color).collect();
+                (image.width(), image.height(), pixels)
+            }
+            Some("ppm") => {
+                let mut bytes = Vec::new();
+                File::open(path)?.read_to_end(&mut bytes)?;
+                decode_ppm(&bytes)?
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unsupported image format: {}", path.display()),
+                ))
+            }
+        };
+        Ok(ImageTexture {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _: &Point3) -> Color {
+        if self.pixels.is_empty() {
+            // Solid cyan makes missing texture data easy to spot
+            return Color::new(0.0, 1.0, 1.0);
+        }
+        // Image rows run top to bottom, while v runs bottom to top
+        let u = u - u.floor();
+        let v = 1.0 - (v - v.floor());
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[j * self.width + i]
+    }
+}
+
+// Undo the gamma 2 encoding that color::clamp_color applies on output
+fn to_linear(value: f64) -> f64 {
+    value * value
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn decode_png(path: &Path) -> io::Result<(usize, usize, Vec<Color>)> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| invalid_data(e.to_string()))?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| invalid_data("PNG is too large"))?;
+    let mut buf = vec![0; size];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| invalid_data(e.to_string()))?;
+
+    let channels = info.color_type.samples();
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in buf.chunks(info.line_size).take(height) {
+        for px in row.chunks(channels).take(width) {
+            let channel = |c: usize| to_linear(px[c] as f64 / 255.0);
+            pixels.push(match channels {
+                1 | 2 => Color::new(channel(0), channel(0), channel(0)),
+                _ => Color::new(channel(0), channel(1), channel(2)),
+            });
+        }
+    }
+    Ok((width, height, pixels))
+}
+
+fn decode_ppm(bytes: &[u8]) -> io::Result<(usize, usize, Vec<Color>)> {
+    // The header is four whitespace-separated tokens, with `#` comments running to end of line
+    let mut pos = 0;
+    let next_token = |pos: &mut usize| -> io::Result<String> {
+        loop {
+            while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+                *pos += 1;
+            }
+            if *pos < bytes.len() && bytes[*pos] == b'#' {
+                while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                    *pos += 1;
+                }
+                continue;
+            }
+            break;
+        }
+        let start = *pos;
+        while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if start == *pos {
+            return Err(invalid_data("unexpected end of PPM data"));
+        }
+        Ok(String::from_utf8_lossy(&bytes[start..*pos]).into_owned())
+    };
+    let parse_number = |token: String| -> io::Result<usize> {
+        token
+            .parse()
+            .map_err(|_| invalid_data(format!("invalid number in PPM header: {}", token)))
+    };
+
+    let magic = next_token(&mut pos)?;
+    let width = parse_number(next_token(&mut pos)?)?;
+    let height = parse_number(next_token(&mut pos)?)?;
+    let max_value = parse_number(next_token(&mut pos)?)?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid_data(format!("invalid PPM max value {}", max_value)));
+    }
+    let scale = 1.0 / max_value as f64;
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or_else(|| invalid_data("PPM image is too large"))?;
+    // Every sample takes at least a byte, so this catches a header promising more pixels than
+    // the file could hold before allocating them
+    if count > bytes.len() - pos {
+        return Err(invalid_data("unexpected end of PPM data"));
+    }
+
+    let samples: Vec<usize> = match magic.as_str() {
+        "P3" => (0..count)
+            .map(|_| next_token(&mut pos).and_then(parse_number))
+            .collect::<io::Result<_>>()?,
+        "P6" => {
+            // Exactly one whitespace byte separates the header from the raster
+            let raster = &bytes[(pos + 1).min(bytes.len())..];
+            let sample_size = if max_value > 255 { 2 } else { 1 };
+            if raster.len() / sample_size < count {
+                return Err(invalid_data("unexpected end of PPM data"));
+            }
+            raster
+                .chunks(sample_size)
+                .take(count)
+                .map(|s| s.iter().fold(0, |acc, &b| (acc << 8) | b as usize))
+                .collect()
+        }
+        _ => return Err(invalid_data(format!("unsupported PPM type {}", magic))),
+    };
+    let pixels = samples
+        .chunks(3)
+        .map(|s| {
+            Color::new(
+                to_linear(s[0] as f64 * scale),
+                to_linear(s[1] as f64 * scale),
+                to_linear(s[2] as f64 * scale),
+            )
+        })
+        .collect();
+    Ok((width, height, pixels))
+}
+
+pub enum NoisePattern {
+    Smooth,
+    Turbulence,
+    // Sine stripes along z whose phase is displaced by turbulence
+    Marble,
+}
+
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+    pattern: NoisePattern,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, pattern: NoisePattern, sampler: &mut Sampler) -> Self {
+        NoiseTexture {
+            noise: Perlin::new(sampler),
+            scale,
+            pattern,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _: f64, _: f64, p: &Point3) -> Color {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let sp = *p * self.scale;
+        match self.pattern {
+            NoisePattern::Smooth => white * 0.5 * (1.0 + self.noise.noise(&sp)),
+            NoisePattern::Turbulence => white * self.noise.turb(&sp, 7),
+            NoisePattern::Marble => {
+                white * 0.5 * (1.0 + f64::sin(sp.z() + 10.0 * self.noise.turb(p, 7)))
+            }
+        }
+    }
+}
+
+// Looks a voxel grid up by position, stretching it over the given box. Lets the albedo or
+// emission of a grid medium vary through its volume.
+pub struct GridTexture {
+    grid: Arc<VoxelGrid>,
+    bounds: Aabb,
+}
+
+impl GridTexture {
+    pub fn new(grid: Arc<VoxelGrid>, bounds: Aabb) -> Self {
+        GridTexture { grid, bounds }
+    }
+}
+
+impl Texture for GridTexture {
+    fn value(&self, _: f64, _: f64, p: &Point3) -> Color {
+        self.grid.color_at(&to_local(&self.bounds, p))
+    }
+}
+
+// Position of p relative to the box, with its corners at (0, 0, 0) and (1, 1, 1)
+pub fn to_local(bounds: &Aabb, p: &Point3) -> Point3 {
+    let extent = bounds.extent();
+    let mut local = *p - bounds.min;
+    for a in 0..3 {
+        local[a] = if extent[a] > 0.0 {
+            local[a] / extent[a]
+        } else {
+            0.5
+        };
+    }
+    local
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_ascii_and_binary_ppm() {
+        let ascii = b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n";
+        let (w, h, pixels) = decode_ppm(ascii).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(pixels[0].x(), 1.0);
+        assert_eq!(pixels[1].z(), 1.0);
+
+        let mut binary = b"P6 1 1 255\n".to_vec();
+        binary.extend_from_slice(&[0, 255, 0]);
+        let (_, _, pixels) = decode_ppm(&binary).unwrap();
+        assert_eq!(pixels[0].y(), 1.0);
+
+        assert!(decode_ppm(b"P6 2 2 255\n\x00").is_err());
+        for header in ["P6 4294967296 4294967296 255\n", "P3 100000 100000 255\n"] {
+            let error = decode_ppm(header.as_bytes()).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
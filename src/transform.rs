@@ -0,0 +1,808 @@
+use std::{ops::Mul, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitFn, HitRecord, Hittable},
+    ray::Ray,
+    rtweekend::degrees_to_radians,
+    sampler::Sampler,
+    vec3::{unit_vector, Point3, Vec3},
+};
+
+// Row-major 4x4 matrix acting on column vectors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Self { m }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Self { m }
+    }
+
+    // Gauss-Jordan elimination with partial pivoting; None for a singular matrix
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Self { m: inv })
+    }
+
+    // Determinant of the upper-left 3x3 block, i.e. of the linear part
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1.0 {
+            Point3::new(x, y, z)
+        } else {
+            Point3::new(x, y, z) / w
+        }
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Self { m }
+    }
+}
+
+// An invertible affine transform, kept together with its inverse
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            matrix: Mat4::identity(),
+            inverse: Mat4::identity(),
+        }
+    }
+
+    // None if the matrix can't be inverted
+    pub fn from_matrix(matrix: Mat4) -> Option<Self> {
+        Some(Self {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        let mut matrix = Mat4::identity();
+        let mut inverse = Mat4::identity();
+        for a in 0..3 {
+            matrix.m[a][3] = offset[a];
+            inverse.m[a][3] = -offset[a];
+        }
+        Self { matrix, inverse }
+    }
+
+    // Scale factors must be non-zero
+    pub fn scale(factors: Vec3) -> Self {
+        let mut matrix = Mat4::identity();
+        let mut inverse = Mat4::identity();
+        for a in 0..3 {
+            matrix.m[a][a] = factors[a];
+            inverse.m[a][a] = 1.0 / factors[a];
+        }
+        Self { matrix, inverse }
+    }
+
+    // Counter-clockwise rotation by angle degrees about axis, looking down the axis towards
+    // the origin (Rodrigues' formula)
+    pub fn rotate(axis: Vec3, angle: f64) -> Self {
+        let a = unit_vector(axis);
+        let theta = degrees_to_radians(angle);
+        let (sin, cos) = theta.sin_cos();
+        let t = 1.0 - cos;
+        let matrix = Mat4::new([
+            [
+                t * a.x() * a.x() + cos,
+                t * a.x() * a.y() - sin * a.z(),
+                t * a.x() * a.z() + sin * a.y(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.y() + sin * a.z(),
+                t * a.y() * a.y() + cos,
+                t * a.y() * a.z() - sin * a.x(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.z() - sin * a.y(),
+                t * a.y() * a.z() + sin * a.x(),
+                t * a.z() * a.z() + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        // A rotation's inverse is its transpose
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    pub fn rotate_x(angle: f64) -> Self {
+        Self::rotate(Vec3::new(1.0, 0.0, 0.0), angle)
+    }
+
+    pub fn rotate_y(angle: f64) -> Self {
+        Self::rotate(Vec3::new(0.0, 1.0, 0.0), angle)
+    }
+
+    pub fn rotate_z(angle: f64) -> Self {
+        Self::rotate(Vec3::new(0.0, 0.0, 1.0), angle)
+    }
+
+    // Euler angles in degrees, applied about x first, then y, then z
+    pub fn rotate_euler(angles: Vec3) -> Self {
+        Self::rotate_x(angles.x())
+            .then(&Self::rotate_y(angles.y()))
+            .then(&Self::rotate_z(angles.z()))
+    }
+
+    // This transform followed by next
+    pub fn then(&self, next: &Transform) -> Self {
+        Self {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn point(&self, p: &Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    // Normals stay perpendicular to the surface under the inverse transpose
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    pub fn ray(&self, r: &Ray) -> Ray {
+        Ray::with_time(
+            self.point(&r.origin()),
+            self.vector(&r.direction()),
+            r.time(),
+        )
+    }
+
+    // Box around all eight transformed corners of bbox
+    pub fn aabb(&self, bbox: &Aabb) -> Aabb {
+        if bbox.is_empty() {
+            return *bbox;
+        }
+        let mut result = Aabb::empty();
+        for corner in bbox.corners() {
+            let p = self.point(&corner);
+            result = result.union(&Aabb::new(p, p));
+        }
+        result
+    }
+}
+
+// Hits the object with r carried into its space, then brings the hit back out to the world
+fn hit_transformed(
+    transform: &Transform,
+    r: &Ray,
+    rec: &mut HitRecord,
+    object_hit: impl FnOnce(&Ray, &mut HitRecord) -> bool,
+) -> bool {
+    // The object-space direction isn't renormalized, so t means the same in both spaces
+    let object_ray = transform.inverse().ray(r);
+    if !object_hit(&object_ray, rec) {
+        return false;
+    }
+    // An affine map keeps the sign of dot(direction, normal), so front_face carries over
+    rec.p = transform.point(&rec.p);
+    rec.normal = unit_vector(transform.normal(&rec.normal));
+    rec.tangent = transform.vector(&rec.tangent);
+    true
+}
+
+// Shares an object, placed in the world by a transform from its own object space
+pub struct TransformedInstance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    bbox: Aabb,
+}
+
+impl TransformedInstance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        let bbox = transform.aabb(&object.bounding_box());
+        Self {
+            object,
+            transform,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for TransformedInstance {
+    fn hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        hit_transformed(&self.transform, r, rec, |r, rec| {
+            self.object.hit(r, t_min, t_max, rec, sampler)
+        })
+    }
+
+    fn hit_surface(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        hit_transformed(&self.transform, r, rec, |r, rec| {
+            self.object.hit_surface(r, t_min, t_max, rec, sampler)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // Directions map between the spaces through the linear part A of the transform. A solid
+    // angle around unit direction d in world space covers |det A^-1| / |A^-1 d|^3 times as
+    // much solid angle in object space.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let inverse = self.transform.inverse();
+        let object_direction = inverse.vector(&unit_vector(*direction));
+        let pdf = self
+            .object
+            .pdf_value(&inverse.point(origin), &object_direction);
+        if pdf == 0.0 {
+            return 0.0;
+        }
+        let length = object_direction.length();
+        pdf * inverse.matrix().determinant3().abs() / (length * length * length)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let object_origin = self.transform.inverse().point(origin);
+        self.transform
+            .vector(&self.object.random(&object_origin, sampler))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> f64 {
+        let object_ray = self.transform.inverse().ray(r);
+        self.object
+            .transmittance(&object_ray, t_min, t_max, sampler)
+    }
+}
+
+// Unit quaternion (w, x, y, z) for interpolating rotations
+#[derive(Clone, Copy, Debug)]
+struct Quat([f64; 4]);
+
+impl Quat {
+    // Shoemake's conversion from a rotation matrix
+    fn from_matrix(m: &Mat4) -> Self {
+        let m = &m.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = f64::sqrt(trace + 1.0) * 2.0;
+            [
+                s / 4.0,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            ]
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = f64::sqrt(1.0 + m[0][0] - m[1][1] - m[2][2]) * 2.0;
+            [
+                (m[2][1] - m[1][2]) / s,
+                s / 4.0,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            ]
+        } else if m[1][1] > m[2][2] {
+            let s = f64::sqrt(1.0 + m[1][1] - m[0][0] - m[2][2]) * 2.0;
+            [
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                s / 4.0,
+                (m[1][2] + m[2][1]) / s,
+            ]
+        } else {
+            let s = f64::sqrt(1.0 + m[2][2] - m[0][0] - m[1][1]) * 2.0;
+            [
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.0,
+            ]
+        };
+        Quat(q).normalized()
+    }
+
+    fn to_matrix(self) -> Mat4 {
+        let [w, x, y, z] = self.0;
+        Mat4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    fn dot(&self, other: &Quat) -> f64 {
+        (0..4).map(|i| self.0[i] * other.0[i]).sum()
+    }
+
+    fn normalized(self) -> Self {
+        let length = self.dot(&self).sqrt();
+        Quat(self.0.map(|c| c / length))
+    }
+
+    // Constant angular speed along the shorter arc from self to other
+    fn slerp(&self, other: &Quat, t: f64) -> Self {
+        let mut cos_theta = self.dot(other);
+        let mut other = *other;
+        if cos_theta < 0.0 {
+            other = Quat(other.0.map(|c| -c));
+            cos_theta = -cos_theta;
+        }
+        if cos_theta > 0.9995 {
+            // Nearly parallel: a normalized lerp is accurate and avoids dividing by sin(theta)
+            let q = std::array::from_fn(|i| self.0[i] + (other.0[i] - self.0[i]) * t);
+            return Quat(q).normalized();
+        }
+        let theta = f64::acos(cos_theta) * t;
+        let perpendicular = Quat(std::array::from_fn(|i| other.0[i] - self.0[i] * cos_theta));
+        let perpendicular = perpendicular.normalized();
+        Quat(std::array::from_fn(|i| {
+            self.0[i] * theta.cos() + perpendicular.0[i] * theta.sin()
+        }))
+    }
+}
+
+// Split an affine matrix into translation, rotation and the remaining scale and shear, so
+// each part can be interpolated on its own
+fn decompose(m: &Mat4) -> (Vec3, Quat, Mat4) {
+    let translation = Vec3::new(m.m[0][3], m.m[1][3], m.m[2][3]);
+    let mut linear = *m;
+    for a in 0..3 {
+        linear.m[a][3] = 0.0;
+        linear.m[3][a] = 0.0;
+    }
+    linear.m[3][3] = 1.0;
+
+    // Polar decomposition: averaging with the inverse transpose converges to the nearest
+    // orthogonal matrix
+    let mut rotation = linear;
+    for _ in 0..100 {
+        let Some(inverse) = rotation.inverse() else {
+            break;
+        };
+        let inverse_transpose = inverse.transpose();
+        let mut next = rotation;
+        let mut change: f64 = 0.0;
+        for i in 0..3 {
+            for j in 0..3 {
+                next.m[i][j] = 0.5 * (rotation.m[i][j] + inverse_transpose.m[i][j]);
+                change = change.max((next.m[i][j] - rotation.m[i][j]).abs());
+            }
+        }
+        rotation = next;
+        if change < 1e-12 {
+            break;
+        }
+    }
+    // A mirror image has to be carried by the scale part, since quaternions can't express it
+    if rotation.determinant3() < 0.0 {
+        for row in rotation.m.iter_mut().take(3) {
+            for value in row.iter_mut().take(3) {
+                *value = -*value;
+            }
+        }
+    }
+    let scale = rotation.transpose() * linear;
+    (translation, Quat::from_matrix(&rotation), scale)
+}
+
+// Moves smoothly from the start transform at time 0 to the end transform at time 1, resting
+// at either end outside of that interval. Translation and scale are interpolated linearly and
+// rotation at a constant angular speed.
+#[derive(Clone, Copy, Debug)]
+pub struct AnimatedTransform {
+    start: Transform,
+    end: Transform,
+    translation: [Vec3; 2],
+    rotation: [Quat; 2],
+    scale: [Mat4; 2],
+    animated: bool,
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Transform, end: Transform) -> Self {
+        let (t0, r0, s0) = decompose(start.matrix());
+        let (t1, r1, s1) = decompose(end.matrix());
+        Self {
+            start,
+            end,
+            translation: [t0, t1],
+            rotation: [r0, r1],
+            scale: [s0, s1],
+            animated: start.matrix != end.matrix,
+        }
+    }
+
+    // None if the transform passes through a degenerate scale at that time
+    pub fn at(&self, time: f64) -> Option<Transform> {
+        let t = time.clamp(0.0, 1.0);
+        if !self.animated || t == 0.0 {
+            return Some(self.start);
+        }
+        if t == 1.0 {
+            return Some(self.end);
+        }
+        let translation = self.translation[0] + (self.translation[1] - self.translation[0]) * t;
+        let rotation = self.rotation[0].slerp(&self.rotation[1], t);
+        let mut scale = self.scale[0];
+        for i in 0..3 {
+            for j in 0..3 {
+                scale.m[i][j] += (self.scale[1].m[i][j] - self.scale[0].m[i][j]) * t;
+            }
+        }
+        let mut matrix = rotation.to_matrix() * scale;
+        for a in 0..3 {
+            matrix.m[a][3] = translation[a];
+        }
+        Transform::from_matrix(matrix)
+    }
+
+    // Box around bbox over the whole motion. The motion is followed in small steps, and the
+    // result is widened by the longest distance a corner moves in one step, which is more than
+    // the path can stray from the sampled positions in between.
+    pub fn aabb(&self, bbox: &Aabb) -> Aabb {
+        if !self.animated || bbox.is_empty() {
+            return self.start.aabb(bbox);
+        }
+        const STEPS: usize = 64;
+        let corners = bbox.corners();
+        let mut result = Aabb::empty();
+        let mut previous: Option<Vec<Point3>> = None;
+        let mut max_step: f64 = 0.0;
+        for step in 0..=STEPS {
+            let Some(transform) = self.at(step as f64 / STEPS as f64) else {
+                continue;
+            };
+            let moved: Vec<Point3> = corners.iter().map(|c| transform.point(c)).collect();
+            for p in &moved {
+                result = result.union(&Aabb::new(*p, *p));
+            }
+            if let Some(previous) = &previous {
+                for (a, b) in previous.iter().zip(&moved) {
+                    max_step = max_step.max((*b - *a).length());
+                }
+            }
+            previous = Some(moved);
+        }
+        let margin = Vec3::new(max_step, max_step, max_step);
+        Aabb::new(result.min - margin, result.max + margin)
+    }
+}
+
+// An instance whose transform changes over the shutter interval, for objects that move,
+// turn or grow
+pub struct MovingInstance {
+    object: Arc<dyn Hittable>,
+    motion: AnimatedTransform,
+    bbox: Aabb,
+}
+
+impl MovingInstance {
+    pub fn new(object: Arc<dyn Hittable>, motion: AnimatedTransform) -> Self {
+        let bbox = motion.aabb(&object.bounding_box());
+        Self {
+            object,
+            motion,
+            bbox,
+        }
+    }
+
+    fn hit_with(
+        &self,
+        hit: HitFn,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        match self.motion.at(r.time()) {
+            Some(transform) => hit_transformed(&transform, r, rec, |r, rec| {
+                hit(self.object.as_ref(), r, t_min, t_max, rec, sampler)
+            }),
+            None => false,
+        }
+    }
+}
+
+// Like a moving sphere it isn't sampled as a light, since where it is depends on the time
+impl Hittable for MovingInstance {
+    fn hit(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        self.hit_with(<dyn Hittable>::hit, r, t_min, t_max, rec, sampler)
+    }
+
+    fn hit_surface(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        self.hit_with(<dyn Hittable>::hit_surface, r, t_min, t_max, rec, sampler)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> f64 {
+        match self.motion.at(r.time()) {
+            Some(transform) => {
+                let object_ray = transform.inverse().ray(r);
+                self.object
+                    .transmittance(&object_ray, t_min, t_max, sampler)
+            }
+            None => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        quad::make_box,
+        rtweekend::{INFINITY, PI},
+        sphere::Sphere,
+        vec3::{dot, random_unit_vector, Color},
+    };
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn composes_and_inverts() {
+        let t = Transform::scale(Vec3::new(2., 3., 4.))
+            .then(&Transform::rotate(Vec3::new(1., 1., 0.), 30.))
+            .then(&Transform::translate(Vec3::new(5., -1., 2.)));
+        let p = Point3::new(0.3, -0.7, 1.1);
+        assert_close(t.inverse().point(&t.point(&p)), p);
+        let general = t.matrix().inverse().unwrap();
+        assert_close(general.transform_point(&t.point(&p)), p);
+
+        assert_close(
+            Transform::rotate_z(90.).point(&Point3::new(1., 0., 0.)),
+            Point3::new(0., 1., 0.),
+        );
+        assert_close(
+            Transform::rotate_euler(Vec3::new(90., 90., 0.)).point(&Point3::new(0., 1., 0.)),
+            Point3::new(1., 0., 0.),
+        );
+        assert!(Transform::from_matrix(Mat4::new([[0.0; 4]; 4])).is_none());
+    }
+
+    #[test]
+    fn instance_transforms_hits_and_bounds() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let cube: Arc<dyn Hittable> = Arc::new(make_box(
+            Point3::new(-1., -1., -1.),
+            Point3::new(1., 1., 1.),
+            material,
+        ));
+        let t = Transform::scale(Vec3::new(1., 2., 1.))
+            .then(&Transform::rotate_y(45.))
+            .then(&Transform::translate(Vec3::new(0., 0., -5.)));
+        let instance = TransformedInstance::new(cube, t);
+
+        let bbox = instance.bounding_box();
+        let half_diagonal = f64::sqrt(2.0);
+        assert!((bbox.max.x() - half_diagonal).abs() < 1e-3);
+        assert!((bbox.max.y() - 2.0).abs() < 1e-3);
+
+        // Straight on, the ray meets the rotated cube's front edge, sqrt(2) in front of center
+        let r = Ray::new(Point3::new(0.1, 1.5, 0.), Vec3::new(0., 0., -1.));
+        let mut rec = HitRecord::default();
+        assert!(instance.hit(&r, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+        assert!((rec.p.z() - (-5.0 + half_diagonal - 0.1)).abs() < 1e-9);
+        assert!((rec.normal.length() - 1.0).abs() < 1e-9);
+        assert!(rec.front_face && dot(rec.normal, r.direction()) < 0.0);
+        assert!((rec.normal.x() - 1.0 / half_diagonal).abs() < 1e-9);
+    }
+
+    #[test]
+    fn instance_pdf_integrates_to_one() {
+        // Sampling a squashed sphere through an instance must still give a normalized density
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere: Arc<dyn Hittable> =
+            Arc::new(Sphere::with_values(Point3::new(0., 0., 0.), 1., material));
+        let t = Transform::scale(Vec3::new(3., 0.5, 1.))
+            .then(&Transform::rotate_x(20.))
+            .then(&Transform::translate(Vec3::new(0., 0., -4.)));
+        let instance = TransformedInstance::new(sphere, t);
+        let origin = Point3::new(0.2, 0.3, 0.);
+
+        let mut sampler = Sampler::new(9);
+        let n = 200_000;
+        let total: f64 = (0..n)
+            .map(|_| instance.pdf_value(&origin, &random_unit_vector(&mut sampler)) * 4.0 * PI)
+            .sum();
+        assert!((total / n as f64 - 1.0).abs() < 0.05);
+
+        for _ in 0..100 {
+            let direction = instance.random(&origin, &mut sampler);
+            assert!(instance.pdf_value(&origin, &direction) > 0.0);
+        }
+    }
+
+    #[test]
+    fn animated_transform_interpolates_rigidly() {
+        let start = Transform::scale(Vec3::new(2., 2., 2.));
+        let end = Transform::scale(Vec3::new(2., 2., 2.))
+            .then(&Transform::rotate_z(90.))
+            .then(&Transform::translate(Vec3::new(0., 0., -4.)));
+        let motion = AnimatedTransform::new(start, end);
+        let p = Point3::new(1., 0., 0.);
+        assert_close(motion.at(0.).unwrap().point(&p), start.point(&p));
+        assert_close(motion.at(1.).unwrap().point(&p), end.point(&p));
+        // Halfway the rotation is 45 degrees, without the shrinking a matrix lerp would cause
+        let half = f64::sqrt(2.0);
+        assert_close(
+            motion.at(0.5).unwrap().point(&p),
+            Point3::new(half, half, -2.),
+        );
+
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let cube: Arc<dyn Hittable> = Arc::new(make_box(
+            Point3::new(0.5, -0.5, -0.5),
+            Point3::new(1.5, 0.5, 0.5),
+            material,
+        ));
+        let instance = MovingInstance::new(cube, motion);
+        let bbox = instance.bounding_box();
+        for step in 0..=100 {
+            let transform = motion.at(step as f64 / 100.0).unwrap();
+            for corner in instance.object.bounding_box().corners() {
+                let q = transform.point(&corner);
+                for a in 0..3 {
+                    assert!(bbox.min[a] <= q[a] && q[a] <= bbox.max[a]);
+                }
+            }
+        }
+
+        // The ray only meets the cube at the times it has swung into its path
+        let r = |time| Ray::with_time(Point3::new(5., 0., 0.), Vec3::new(-1., 0., 0.), time);
+        let mut rec = HitRecord::default();
+        assert!(instance.hit(&r(0.0), 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+        assert!((rec.t - 2.0).abs() < 1e-9);
+        assert!((rec.normal.x() - 1.0).abs() < 1e-9);
+        assert!(!instance.hit(&r(1.0), 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+    }
+}
@@ -0,0 +1,398 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    bvh::split,
+    hittable::{planar_pdf, HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    rtweekend::INFINITY,
+    sampler::Sampler,
+    vec3::{cross, dot, unit_vector, Point3, Vec3},
+};
+
+// Vertex buffers shared by every triangle of a mesh. Normals and UVs, when present,
+// are indexed by the same vertex indices as the positions.
+pub struct Mesh {
+    pub positions: Vec<Point3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<(f64, f64)>>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Point3>, indices: Vec<[usize; 3]>) -> Self {
+        Self {
+            positions,
+            normals: None,
+            uvs: None,
+            indices,
+        }
+    }
+
+    fn vertices(&self, face: usize) -> [Point3; 3] {
+        let [i0, i1, i2] = self.indices[face];
+        [self.positions[i0], self.positions[i1], self.positions[i2]]
+    }
+
+    fn face_bounds(&self, face: usize) -> Aabb {
+        let [p0, p1, p2] = self.vertices(face);
+        Aabb::new(p0, p1).union(&Aabb::new(p2, p2)).pad(1e-4)
+    }
+
+    // Intersects r with one face, filling in everything in rec but the material
+    fn hit_face(&self, face: usize, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // Watertight ray/triangle intersection (Woop, Benthin and Wald, JCGT 2013).
+        // The triangle is sheared into a space where the ray points down +z, so edges
+        // shared between neighbouring triangles are evaluated identically and no ray can
+        // slip through the crack between them.
+        let [p0, p1, p2] = self.vertices(face);
+        let dir = r.direction();
+
+        let kz = (0..3)
+            .max_by(|&a, &b| dir[a].abs().total_cmp(&dir[b].abs()))
+            .unwrap();
+        let mut kx = (kz + 1) % 3;
+        let mut ky = (kx + 1) % 3;
+        if dir[kz] < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+        let sx = dir[kx] / dir[kz];
+        let sy = dir[ky] / dir[kz];
+        let sz = 1.0 / dir[kz];
+
+        let a = p0 - r.origin();
+        let b = p1 - r.origin();
+        let c = p2 - r.origin();
+        let ax = a[kx] - sx * a[kz];
+        let ay = a[ky] - sy * a[kz];
+        let bx = b[kx] - sx * b[kz];
+        let by = b[ky] - sy * b[kz];
+        let cx = c[kx] - sx * c[kz];
+        let cy = c[ky] - sy * c[kz];
+
+        // Scaled barycentric coordinates, as signed edge functions
+        let e0 = bx * cy - by * cx;
+        let e1 = cx * ay - cy * ax;
+        let e2 = ax * by - ay * bx;
+        if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+            return false;
+        }
+        let det = e0 + e1 + e2;
+        if det == 0.0 {
+            return false;
+        }
+
+        let az = sz * a[kz];
+        let bz = sz * b[kz];
+        let cz = sz * c[kz];
+        let t = (e0 * az + e1 * bz + e2 * cz) / det;
+        if t < t_min || t_max < t {
+            return false;
+        }
+
+        let barycentric = Vec3::new(e0 / det, e1 / det, e2 / det);
+        let [i0, i1, i2] = self.indices[face];
+        // Without UVs, u and v are the weights of the second and third vertices
+        let uv = match &self.uvs {
+            Some(uvs) => [uvs[i0], uvs[i1], uvs[i2]],
+            None => [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+        };
+
+        rec.t = t;
+        rec.p = p0 * barycentric[0] + p1 * barycentric[1] + p2 * barycentric[2];
+        rec.barycentric = barycentric;
+        rec.u = uv[0].0 * barycentric[0] + uv[1].0 * barycentric[1] + uv[2].0 * barycentric[2];
+        rec.v = uv[0].1 * barycentric[0] + uv[1].1 * barycentric[1] + uv[2].1 * barycentric[2];
+        rec.tangent = tangent(&[p0, p1, p2], &uv);
+
+        // front_face always follows the geometric normal; an interpolated shading normal is
+        // only allowed to bend the normal within that same hemisphere
+        let geometric_normal = unit_vector(cross(p1 - p0, p2 - p0));
+        rec.set_face_normal(r, &geometric_normal);
+        if let Some(normals) = &self.normals {
+            let mut shading_normal = unit_vector(
+                normals[i0] * barycentric[0]
+                    + normals[i1] * barycentric[1]
+                    + normals[i2] * barycentric[2],
+            );
+            if dot(shading_normal, geometric_normal) < 0.0 {
+                shading_normal = -shading_normal;
+            }
+            rec.normal = if rec.front_face {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+        true
+    }
+}
+
+#[derive(Clone)]
+pub struct Triangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+    mat_ptr: Option<Arc<dyn Material>>,
+}
+
+impl Triangle {
+    pub fn with_values(p0: Point3, p1: Point3, p2: Point3, m: Arc<dyn Material>) -> Self {
+        let mesh = Mesh::new(vec![p0, p1, p2], vec![[0, 1, 2]]);
+        Self::from_mesh(Arc::new(mesh), 0, m)
+    }
+
+    pub fn from_mesh(mesh: Arc<Mesh>, face: usize, m: Arc<dyn Material>) -> Self {
+        Self {
+            mesh,
+            face,
+            mat_ptr: Some(m),
+        }
+    }
+
+    fn vertices(&self) -> [Point3; 3] {
+        self.mesh.vertices(self.face)
+    }
+}
+
+// Derivative of position with respect to u across a triangle, solved from how u and v change
+// along two of its edges. Zero when the UVs are degenerate.
+fn tangent(p: &[Point3; 3], uv: &[(f64, f64); 3]) -> Vec3 {
+    let (du02, dv02) = (uv[0].0 - uv[2].0, uv[0].1 - uv[2].1);
+    let (du12, dv12) = (uv[1].0 - uv[2].0, uv[1].1 - uv[2].1);
+    let det = du02 * dv12 - dv02 * du12;
+    if det.abs() < 1e-12 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    ((p[0] - p[2]) * dv12 - (p[1] - p[2]) * dv02) / det
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord, _: &mut Sampler) -> bool {
+        if !self.mesh.hit_face(self.face, r, t_min, t_max, rec) {
+            return false;
+        }
+        rec.mat_ptr = self.mat_ptr.clone();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.mesh.face_bounds(self.face)
+    }
+
+    // Points are sampled uniformly by area
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let r = Ray::new(*origin, *direction);
+        let mut rec = HitRecord::default();
+        if !self.hit(&r, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)) {
+            return 0.0;
+        }
+        let [p0, p1, p2] = self.vertices();
+        let n = cross(p1 - p0, p2 - p0);
+        planar_pdf(&r, rec.t, &n, 0.5 * n.length())
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut Sampler) -> Vec3 {
+        let [p0, p1, p2] = self.vertices();
+        let s = sampler.random_double().sqrt();
+        let r = sampler.random_double();
+        let p = p0 * (1.0 - s) + p1 * (s * (1.0 - r)) + p2 * (s * r);
+        p - *origin
+    }
+}
+
+// A mesh of triangles sharing one vertex buffer and one material, accelerated by its own BVH.
+// The BVH is built over face indices, so faces aren't boxed as separate objects.
+pub struct TriangleMesh {
+    mesh: Arc<Mesh>,
+    material: Arc<dyn Material>,
+    nodes: Vec<FaceNode>, // Depth first, so a node's left child directly follows it
+}
+
+enum FaceNode {
+    Leaf { bbox: Aabb, face: usize },
+    Interior { bbox: Aabb, right: usize }, // Index of the right child
+}
+
+impl TriangleMesh {
+    pub fn new(mesh: Arc<Mesh>, m: Arc<dyn Material>) -> Self {
+        let faces: Vec<usize> = (0..mesh.indices.len()).collect();
+        let mut nodes = Vec::with_capacity(2 * faces.len());
+        if !faces.is_empty() {
+            build_faces(&mesh, faces, &mut nodes);
+        }
+        Self {
+            mesh,
+            material: m,
+            nodes,
+        }
+    }
+
+    fn hit_node(&self, node: usize, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match self.nodes[node] {
+            FaceNode::Leaf { bbox, face } => {
+                bbox.hit(r, t_min, t_max) && self.mesh.hit_face(face, r, t_min, t_max, rec)
+            }
+            FaceNode::Interior { bbox, right } => {
+                if !bbox.hit(r, t_min, t_max) {
+                    return false;
+                }
+                let hit_left = self.hit_node(node + 1, r, t_min, t_max, rec);
+                let t_max = if hit_left { rec.t } else { t_max };
+                let hit_right = self.hit_node(right, r, t_min, t_max, rec);
+                hit_left || hit_right
+            }
+        }
+    }
+}
+
+// Appends the subtree over faces to nodes, depth first
+fn build_faces(mesh: &Mesh, mut faces: Vec<usize>, nodes: &mut Vec<FaceNode>) {
+    if faces.len() == 1 {
+        let face = faces.pop().unwrap();
+        nodes.push(FaceNode::Leaf {
+            bbox: mesh.face_bounds(face),
+            face,
+        });
+        return;
+    }
+    let bbox = faces
+        .iter()
+        .fold(Aabb::empty(), |b, &f| b.union(&mesh.face_bounds(f)));
+    let (left, right) = split(faces, &bbox, |&f| mesh.face_bounds(f));
+    let index = nodes.len();
+    nodes.push(FaceNode::Interior { bbox, right: 0 });
+    build_faces(mesh, left, nodes);
+    let right_index = nodes.len();
+    nodes[index] = FaceNode::Interior {
+        bbox,
+        right: right_index,
+    };
+    build_faces(mesh, right, nodes);
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord, _: &mut Sampler) -> bool {
+        if self.nodes.is_empty() || !self.hit_node(0, r, t_min, t_max, rec) {
+            return false;
+        }
+        rec.mat_ptr = Some(self.material.clone());
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(FaceNode::Leaf { bbox, .. } | FaceNode::Interior { bbox, .. }) => *bbox,
+            None => Aabb::empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        vec3::{unit_vector, Color},
+    };
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn hit_reports_barycentrics_and_normal() {
+        let tri = Triangle::with_values(
+            Point3::new(0., 0., -1.),
+            Point3::new(1., 0., -1.),
+            Point3::new(0., 1., -1.),
+            material(),
+        );
+        let r = Ray::new(Point3::new(0.25, 0.5, 0.), Vec3::new(0., 0., -1.));
+        let mut rec = HitRecord::default();
+        assert!(tri.hit(&r, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.barycentric[1] - 0.25).abs() < 1e-12);
+        assert!((rec.barycentric[2] - 0.5).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.normal.z() - 1.0).abs() < 1e-12);
+        assert!((rec.tangent - Vec3::new(1., 0., 0.)).length() < 1e-12);
+
+        // The tangent follows u wherever the UVs put it
+        let mut mesh = Mesh::new(
+            vec![
+                Point3::new(0., 0., -1.),
+                Point3::new(1., 0., -1.),
+                Point3::new(0., 1., -1.),
+            ],
+            vec![[0, 1, 2]],
+        );
+        mesh.uvs = Some(vec![(0., 0.), (0., 1.), (2., 0.)]);
+        let mapped = Triangle::from_mesh(Arc::new(mesh), 0, material());
+        assert!(mapped.hit(&r, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+        assert!((rec.tangent - Vec3::new(0., 0.5, 0.)).length() < 1e-12);
+
+        let miss = Ray::new(Point3::new(0.75, 0.75, 0.), Vec3::new(0., 0., -1.));
+        assert!(!tri.hit(&miss, 0.001, INFINITY, &mut rec, &mut Sampler::new(0)));
+    }
+
+    #[test]
+    fn shared_edge_is_watertight() {
+        // A quad split along its diagonal; rays aimed exactly at the diagonal must hit
+        let mesh = Arc::new(Mesh::new(
+            vec![
+                Point3::new(0., 0., 0.),
+                Point3::new(1., 0., 0.),
+                Point3::new(1., 1., 0.),
+                Point3::new(0., 1., 0.),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        ));
+        let quad = TriangleMesh::new(mesh, material());
+        let origin = Point3::new(0.3, -0.2, 2.7);
+        let mut sampler = Sampler::new(1);
+        for _ in 0..1000 {
+            let s = sampler.random_double();
+            let target = Point3::new(s, s, 0.);
+            let r = Ray::new(origin, target - origin);
+            let mut rec = HitRecord::default();
+            assert!(quad.hit(&r, 0.001, INFINITY, &mut rec, &mut sampler));
+        }
+    }
+
+    #[test]
+    fn mesh_matches_separate_triangles() {
+        let mut sampler = Sampler::new(3);
+        let positions: Vec<Point3> = (0..300)
+            .map(|_| Vec3::random_range(&mut sampler, -5.0, 5.0))
+            .collect();
+        let indices: Vec<[usize; 3]> = (0..100).map(|i| [3 * i, 3 * i + 1, 3 * i + 2]).collect();
+        let mesh = Arc::new(Mesh::new(positions, indices));
+        let whole = TriangleMesh::new(mesh.clone(), material());
+        let faces: Vec<Triangle> = (0..100)
+            .map(|f| Triangle::from_mesh(mesh.clone(), f, material()))
+            .collect();
+
+        for _ in 0..2000 {
+            let origin = Vec3::random_range(&mut sampler, -8.0, 8.0);
+            let direction = unit_vector(Vec3::random_range(&mut sampler, -1.0, 1.0));
+            let r = Ray::new(origin, direction);
+            let mut rec = HitRecord::default();
+            let mut closest = INFINITY;
+            for tri in &faces {
+                if tri.hit(&r, 0.001, closest, &mut rec, &mut sampler) {
+                    closest = rec.t;
+                }
+            }
+            let mut mesh_rec = HitRecord::default();
+            assert_eq!(
+                whole.hit(&r, 0.001, INFINITY, &mut mesh_rec, &mut sampler),
+                closest < INFINITY
+            );
+            if closest < INFINITY {
+                assert_eq!(mesh_rec.t, closest);
+                assert!(mesh_rec.mat_ptr.is_some());
+            }
+        }
+    }
+}
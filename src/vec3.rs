@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub};
 
-use crate::rtweekend::{random_double, random_double_range};
+use crate::sampler::Sampler;
 
 pub type Point3 = Vec3; // 3D point
 pub type Color = Vec3; // RGB color
@@ -48,16 +48,20 @@ impl Vec3 {
         self.e.iter().all(|&val| val.abs() < s)
     }
 
-    pub fn random() -> Self {
-        return Vec3::new(random_double(), random_double(), random_double());
+    pub fn random(sampler: &mut Sampler) -> Self {
+        Vec3::new(
+            sampler.random_double(),
+            sampler.random_double(),
+            sampler.random_double(),
+        )
     }
 
-    pub fn random_range(min: f64, max: f64) -> Self {
-        return Vec3::new(
-            random_double_range(min, max),
-            random_double_range(min, max),
-            random_double_range(min, max),
-        );
+    pub fn random_range(sampler: &mut Sampler, min: f64, max: f64) -> Self {
+        Vec3::new(
+            sampler.random_double_range(min, max),
+            sampler.random_double_range(min, max),
+            sampler.random_double_range(min, max),
+        )
     }
 }
 
@@ -76,14 +80,14 @@ pub fn cross(u: Vec3, v: Vec3) -> Vec3 {
 }
 
 pub fn unit_vector(v: Vec3) -> Vec3 {
-    return v / v.length();
+    v / v.length()
 }
 
-pub fn random_in_unit_disk() -> Vec3 {
+pub fn random_in_unit_disk(sampler: &mut Sampler) -> Vec3 {
     loop {
         let p = Vec3::new(
-            random_double_range(-1., 1.),
-            random_double_range(-1., 1.),
+            sampler.random_double_range(-1., 1.),
+            sampler.random_double_range(-1., 1.),
             0.,
         );
         if p.length_squared() < 1. {
@@ -92,9 +96,9 @@ pub fn random_in_unit_disk() -> Vec3 {
     }
 }
 
-pub fn random_in_unit_sphere() -> Vec3 {
+pub fn random_in_unit_sphere(sampler: &mut Sampler) -> Vec3 {
     loop {
-        let p = Vec3::random_range(-1.0, 1.0);
+        let p = Vec3::random_range(sampler, -1.0, 1.0);
         if p.length_squared() >= 1.0 {
             continue;
         }
@@ -102,12 +106,12 @@ pub fn random_in_unit_sphere() -> Vec3 {
     }
 }
 
-pub fn random_unit_vector() -> Vec3 {
-    unit_vector(random_in_unit_sphere())
+pub fn random_unit_vector(sampler: &mut Sampler) -> Vec3 {
+    unit_vector(random_in_unit_sphere(sampler))
 }
 
-pub fn _random_in_hemisphere(normal: Vec3) -> Vec3 {
-    let in_unit_sphere = random_in_unit_sphere();
+pub fn _random_in_hemisphere(sampler: &mut Sampler, normal: Vec3) -> Vec3 {
+    let in_unit_sphere = random_in_unit_sphere(sampler);
     if dot(in_unit_sphere, normal) > 0.0 {
         return in_unit_sphere;
     }
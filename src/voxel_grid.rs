@@ -0,0 +1,235 @@
+use std::{fs, io, path::Path};
+
+use crate::{
+    aabb::Aabb,
+    vec3::{Color, Point3},
+};
+
+// A dense 3D grid of one or three channel values, e.g. the density of a cloud. Voxel centers
+// sit at (i + 0.5) / nx etc. inside the unit cube, and lookups interpolate trilinearly.
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    channels: usize,
+    values: Vec<f64>, // x varies fastest, then y, then z, with channels interleaved
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3], channels: usize, values: Vec<f64>) -> Self {
+        assert!(channels == 1 || channels == 3, "expected 1 or 3 channels");
+        assert_eq!(Some(values.len()), value_count(resolution, channels));
+        VoxelGrid {
+            resolution,
+            channels,
+            values,
+        }
+    }
+
+    // Reads a .vol file, returning the grid together with the bounds stored in the file, or a
+    // raw file of little-endian 32-bit floats, which has to come with its resolution
+    pub fn load(path: &Path, resolution: Option<[usize; 3]>) -> io::Result<(Self, Option<Aabb>)> {
+        let bytes = fs::read(path)?;
+        match resolution {
+            Some(resolution) => Ok((decode_raw(&bytes, resolution)?, None)),
+            None if bytes.starts_with(b"VOL") => {
+                let (grid, bounds) = decode_vol(&bytes)?;
+                Ok((grid, Some(bounds)))
+            }
+            None => Err(invalid_data(
+                "not a .vol file; raw grids need their resolution",
+            )),
+        }
+    }
+
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    // Largest value of the first channel anywhere in the grid
+    pub fn max_value(&self) -> f64 {
+        self.values
+            .iter()
+            .step_by(self.channels)
+            .fold(0.0, |m, &v| f64::max(m, v))
+    }
+
+    // First channel at a point of the unit cube
+    pub fn scalar_at(&self, local: &Point3) -> f64 {
+        self.interpolate(local, 0)
+    }
+
+    // All channels at a point of the unit cube, with a single channel repeated as gray
+    pub fn color_at(&self, local: &Point3) -> Color {
+        if self.channels == 1 {
+            let v = self.interpolate(local, 0);
+            return Color::new(v, v, v);
+        }
+        Color::new(
+            self.interpolate(local, 0),
+            self.interpolate(local, 1),
+            self.interpolate(local, 2),
+        )
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize, channel: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.values[((z * ny + y) * nx + x) * self.channels + channel]
+    }
+
+    fn interpolate(&self, local: &Point3, channel: usize) -> f64 {
+        // Index and weight of the lower neighbour along each axis, clamped at the edges
+        let lower = |a: usize| {
+            let n = self.resolution[a];
+            let x = (local[a] * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            let i = (x.floor() as usize).min(n.saturating_sub(2));
+            (i, (i + 1).min(n - 1), x - i as f64)
+        };
+        let (x0, x1, fx) = lower(0);
+        let (y0, y1, fy) = lower(1);
+        let (z0, z1, fz) = lower(2);
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let plane = |z: usize| {
+            lerp(
+                lerp(
+                    self.voxel(x0, y0, z, channel),
+                    self.voxel(x1, y0, z, channel),
+                    fx,
+                ),
+                lerp(
+                    self.voxel(x0, y1, z, channel),
+                    self.voxel(x1, y1, z, channel),
+                    fx,
+                ),
+                fy,
+            )
+        };
+        lerp(plane(z0), plane(z1), fz)
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// Number of values a grid of the given size holds, or None if that overflows
+fn value_count(resolution: [usize; 3], channels: usize) -> Option<usize> {
+    resolution
+        .iter()
+        .try_fold(channels, |count, &n| count.checked_mul(n))
+}
+
+fn read_f32s(bytes: &[u8]) -> Vec<f64> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+        .collect()
+}
+
+fn decode_raw(bytes: &[u8], resolution: [usize; 3]) -> io::Result<VoxelGrid> {
+    let voxels =
+        value_count(resolution, 1).ok_or_else(|| invalid_data("grid resolution is too large"))?;
+    if voxels == 0 {
+        return Err(invalid_data("grid resolution must not be zero"));
+    }
+    if voxels > bytes.len() / 4 {
+        return Err(invalid_data(format!(
+            "{} bytes are too few for a {}x{}x{} grid",
+            bytes.len(),
+            resolution[0],
+            resolution[1],
+            resolution[2]
+        )));
+    }
+    let values = read_f32s(bytes);
+    let channels = values.len() / voxels;
+    if !bytes.len().is_multiple_of(4)
+        || !values.len().is_multiple_of(voxels)
+        || !(channels == 1 || channels == 3)
+    {
+        return Err(invalid_data(format!(
+            "{} bytes don't hold a {}x{}x{} grid of 1 or 3 channel floats",
+            bytes.len(),
+            resolution[0],
+            resolution[1],
+            resolution[2]
+        )));
+    }
+    Ok(VoxelGrid::new(resolution, channels, values))
+}
+
+// Mitsuba's grid volume format: "VOL", version 3, encoding 1 (32-bit floats), the resolution
+// and channel count as 32-bit integers, the bounds as six floats, then the values
+fn decode_vol(bytes: &[u8]) -> io::Result<(VoxelGrid, Aabb)> {
+    const HEADER: usize = 48;
+    if bytes.len() < HEADER || &bytes[0..3] != b"VOL" || bytes[3] != 3 {
+        return Err(invalid_data("unsupported .vol header"));
+    }
+    let int =
+        |at: usize| i32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    if int(4) != 1 {
+        return Err(invalid_data("only 32-bit float .vol files are supported"));
+    }
+    let dims = [int(8), int(12), int(16), int(20)];
+    if dims.iter().any(|&d| d <= 0) || !(dims[3] == 1 || dims[3] == 3) {
+        return Err(invalid_data("invalid .vol resolution or channel count"));
+    }
+    let resolution = [dims[0] as usize, dims[1] as usize, dims[2] as usize];
+    let channels = dims[3] as usize;
+    let expected = value_count(resolution, channels)
+        .ok_or_else(|| invalid_data(".vol resolution is too large"))?;
+    if expected > (bytes.len() - HEADER) / 4 {
+        return Err(invalid_data("unexpected end of .vol data"));
+    }
+    let bounds = read_f32s(&bytes[24..HEADER]);
+    let values = read_f32s(&bytes[HEADER..]);
+    let grid = VoxelGrid::new(resolution, channels, values[..expected].to_vec());
+    let bounds = Aabb::new(
+        Point3::new(bounds[0], bounds[1], bounds[2]),
+        Point3::new(bounds[3], bounds[4], bounds[5]),
+    );
+    Ok((grid, bounds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_and_interpolates() {
+        let mut vol = b"VOL\x03".to_vec();
+        for i in [1, 2, 1, 1, 1] {
+            vol.extend_from_slice(&i32::to_le_bytes(i));
+        }
+        for f in [0.0f32, 0.0, 0.0, 2.0, 1.0, 1.0, 0.0, 4.0] {
+            vol.extend_from_slice(&f.to_le_bytes());
+        }
+        let (grid, bounds) = decode_vol(&vol).unwrap();
+        assert_eq!(grid.resolution(), [2, 1, 1]);
+        assert_eq!(bounds.max.x(), 2.0);
+        assert_eq!(grid.max_value(), 4.0);
+        // Voxel centers are at x = 0.25 and 0.75, and values clamp beyond them
+        assert_eq!(grid.scalar_at(&Point3::new(0.25, 0.5, 0.5)), 0.0);
+        assert_eq!(grid.scalar_at(&Point3::new(0.5, 0.5, 0.5)), 2.0);
+        assert_eq!(grid.scalar_at(&Point3::new(1.0, 0.5, 0.5)), 4.0);
+
+        let raw: Vec<u8> = [1.0f32, 2.0, 3.0]
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .collect();
+        let grid = decode_raw(&raw, [1, 1, 1]).unwrap();
+        assert_eq!(grid.color_at(&Point3::new(0.5, 0.5, 0.5)).z(), 3.0);
+        assert!(decode_raw(&raw, [2, 1, 1]).is_err());
+        assert!(decode_raw(&raw, [1 << 32, 1 << 32, 2]).is_err());
+
+        // A header whose resolution overflows, or needs more data than follows, is rejected
+        for dims in [[i32::MAX, i32::MAX, i32::MAX, 3], [2, 2, 2, 1]] {
+            let mut huge = b"VOL\x03".to_vec();
+            huge.extend_from_slice(&i32::to_le_bytes(1));
+            for d in dims {
+                huge.extend_from_slice(&i32::to_le_bytes(d));
+            }
+            huge.extend_from_slice(&[0; 24 + 4]);
+            let error = decode_vol(&huge).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
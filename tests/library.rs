@@ -0,0 +1,47 @@
+// Builds and renders a scene through the public API only, the way a downstream tool would
+use std::sync::Arc;
+
+use rust_ray_tracing::{
+    background::Background,
+    camera::{Camera, CameraSettings},
+    hittable_list::HittableList,
+    material::Lambertian,
+    scene::Scene,
+    sphere::Sphere,
+    vec3::{Color, Point3},
+};
+
+#[test]
+fn renders_into_an_image() {
+    let mut world = HittableList::new();
+    world.add(Box::new(Sphere::with_values(
+        Point3::new(0., 0., -1.),
+        0.5,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+    let scene = Scene::new(
+        world,
+        HittableList::new(),
+        Background::Solid(Color::new(1., 1., 1.)),
+    );
+    let camera = Camera::new(&CameraSettings {
+        image_width: 8,
+        samples_per_pixel: 2,
+        ..CameraSettings::default()
+    });
+
+    let image = camera.render(&scene);
+    assert_eq!((image.width(), image.height()), (8, 8));
+    // The sphere covers the middle of the frame, the background the corners. A visible
+    // background is opaque; a hidden one leaves the corners transparent and black.
+    assert_eq!(image.get(4, 4).alpha, 1.0);
+    assert_eq!(image.get(0, 0).alpha, 1.0);
+    assert_eq!(image.get(0, 0).color.x(), 1.0);
+
+    let mut scene = scene;
+    scene.background_visible = false;
+    let image = camera.render(&scene);
+    assert_eq!(image.get(4, 4).alpha, 1.0);
+    assert_eq!(image.get(0, 0).alpha, 0.0);
+    assert_eq!(image.get(0, 0).color.x(), 0.0);
+}